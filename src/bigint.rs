@@ -0,0 +1,458 @@
+use std::cmp::Ordering;
+use std::convert::TryFrom;
+use std::fmt;
+
+/// an arbitrary-precision integer
+///
+/// stored as a sign and a magnitude of base 2^32 digits, least significant first, with no
+/// trailing zero digits. zero is never negative
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub struct BigInt {
+    negative: bool,
+    magnitude: Vec<u32>,
+}
+
+const BASE: u64 = 1 << 32;
+
+/// largest power of ten that fits in a digit, used when converting to and from decimal
+const DECIMAL_CHUNK: u32 = 1_000_000_000;
+const DECIMAL_CHUNK_DIGITS: usize = 9;
+
+impl BigInt {
+    pub fn zero() -> Self {
+        Self {
+            negative: false,
+            magnitude: Vec::new(),
+        }
+    }
+
+    fn from_parts(negative: bool, mut magnitude: Vec<u32>) -> Self {
+        while magnitude.last() == Some(&0) {
+            magnitude.pop();
+        }
+        let negative = negative && !magnitude.is_empty();
+        Self {
+            negative,
+            magnitude,
+        }
+    }
+
+    /// parse an optionally signed string of decimal digits
+    pub fn parse(text: &str) -> Option<Self> {
+        let (negative, digits) = match text.strip_prefix('-') {
+            Some(digits) => (true, digits),
+            None => (false, text),
+        };
+        if digits.is_empty() || !digits.bytes().all(|b| b.is_ascii_digit()) {
+            return None;
+        }
+
+        // take digits a chunk at a time, starting with the leftover at the front
+        let mut magnitude = Vec::new();
+        let first = match digits.len() % DECIMAL_CHUNK_DIGITS {
+            0 => DECIMAL_CHUNK_DIGITS,
+            len => len,
+        };
+        let mut start = 0;
+        let mut end = first;
+        while start < digits.len() {
+            let chunk: u32 = digits[start..end].parse().ok()?;
+            let scale = 10u32.pow((end - start) as u32);
+            mul_small_add(&mut magnitude, scale, chunk);
+            start = end;
+            end += DECIMAL_CHUNK_DIGITS;
+        }
+        Some(Self::from_parts(negative, magnitude))
+    }
+
+    /// parse unsigned digits in the given radix, which must already have been checked
+    pub fn parse_radix(digits: &str, radix: u32) -> Self {
+        let mut magnitude = Vec::new();
+        for c in digits.chars() {
+            let digit = c
+                .to_digit(radix)
+                .expect("digits should have been validated");
+            mul_small_add(&mut magnitude, radix, digit);
+        }
+        Self::from_parts(false, magnitude)
+    }
+
+    pub fn is_negative(&self) -> bool {
+        self.negative
+    }
+
+    pub fn is_odd(&self) -> bool {
+        self.magnitude.first().is_some_and(|digit| digit & 1 == 1)
+    }
+
+    pub fn is_zero(&self) -> bool {
+        self.magnitude.is_empty()
+    }
+
+    pub fn to_i64(&self) -> Option<i64> {
+        if self.magnitude.len() > 2 {
+            return None;
+        }
+        let magnitude = self
+            .magnitude
+            .iter()
+            .rev()
+            .fold(0u64, |acc, &digit| (acc << 32) | digit as u64);
+        if self.negative {
+            if magnitude <= i64::MIN.unsigned_abs() {
+                Some((magnitude as i64).wrapping_neg())
+            } else {
+                None
+            }
+        } else {
+            i64::try_from(magnitude).ok()
+        }
+    }
+
+    /// the nearest float, or an infinity if the value is too large
+    pub fn to_f64(&self) -> f64 {
+        // only the top three digits can affect the result, but summing all of them is simpler
+        let magnitude = self
+            .magnitude
+            .iter()
+            .rev()
+            .fold(0.0, |acc, &digit| acc * BASE as f64 + digit as f64);
+        if self.negative {
+            -magnitude
+        } else {
+            magnitude
+        }
+    }
+
+    /// the exact value of a float, if it is a finite integer
+    pub fn from_f64(num: f64) -> Option<Self> {
+        if !num.is_finite() || num.fract() != 0.0 {
+            return None;
+        }
+        // every integral float is mantissa * 2^exponent, with exponent >= 0 once it is this big
+        if num.abs() < (1u64 << 53) as f64 {
+            return Some(Self::from(num as i64));
+        }
+        let bits = num.to_bits();
+        let exponent = ((bits >> 52) & 0x7ff) as u32 - 1075;
+        let mantissa = (bits & ((1 << 52) - 1)) | (1 << 52);
+        let mut magnitude = vec![0; (exponent / 32) as usize];
+        let shift = exponent % 32;
+        let shifted = (mantissa as u128) << shift;
+        magnitude.extend_from_slice(&[
+            shifted as u32,
+            (shifted >> 32) as u32,
+            (shifted >> 64) as u32,
+        ]);
+        Some(Self::from_parts(num < 0.0, magnitude))
+    }
+
+    /// the number of bits in the magnitude, which is 0 for zero
+    pub fn bits(&self) -> u64 {
+        match self.magnitude.last() {
+            Some(top) => 32 * self.magnitude.len() as u64 - u64::from(top.leading_zeros()),
+            None => 0,
+        }
+    }
+
+    pub fn pow(&self, mut exponent: u32) -> Self {
+        let mut result = Self::from(1);
+        let mut base = self.clone();
+        while exponent > 0 {
+            if exponent & 1 == 1 {
+                result = &result * &base;
+            }
+            exponent >>= 1;
+            if exponent > 0 {
+                base = &base * &base;
+            }
+        }
+        result
+    }
+
+    /// floored division, so the remainder takes the sign of the divisor. `None` when dividing
+    /// by zero
+    pub fn div_mod(&self, other: &Self) -> Option<(Self, Self)> {
+        if other.is_zero() {
+            return None;
+        }
+        let (quotient, remainder) = div_rem_magnitude(&self.magnitude, &other.magnitude);
+        let quotient = Self::from_parts(self.negative != other.negative, quotient);
+        let remainder = Self::from_parts(self.negative, remainder);
+        if !remainder.is_zero() && remainder.negative != other.negative {
+            Some((&quotient - &Self::from(1), &remainder + other))
+        } else {
+            Some((quotient, remainder))
+        }
+    }
+}
+
+impl From<i64> for BigInt {
+    fn from(num: i64) -> Self {
+        let magnitude = num.unsigned_abs();
+        Self::from_parts(num < 0, vec![magnitude as u32, (magnitude >> 32) as u32])
+    }
+}
+
+impl Ord for BigInt {
+    fn cmp(&self, other: &Self) -> Ordering {
+        match (self.negative, other.negative) {
+            (false, true) => Ordering::Greater,
+            (true, false) => Ordering::Less,
+            (false, false) => cmp_magnitude(&self.magnitude, &other.magnitude),
+            (true, true) => cmp_magnitude(&other.magnitude, &self.magnitude),
+        }
+    }
+}
+
+impl PartialOrd for BigInt {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl std::ops::Neg for &BigInt {
+    type Output = BigInt;
+
+    fn neg(self) -> BigInt {
+        BigInt::from_parts(!self.negative, self.magnitude.clone())
+    }
+}
+
+impl std::ops::Add for &BigInt {
+    type Output = BigInt;
+
+    fn add(self, other: &BigInt) -> BigInt {
+        if self.negative == other.negative {
+            return BigInt::from_parts(
+                self.negative,
+                add_magnitude(&self.magnitude, &other.magnitude),
+            );
+        }
+        // opposite signs, so subtract the smaller magnitude from the larger
+        match cmp_magnitude(&self.magnitude, &other.magnitude) {
+            Ordering::Less => BigInt::from_parts(
+                other.negative,
+                sub_magnitude(&other.magnitude, &self.magnitude),
+            ),
+            _ => BigInt::from_parts(
+                self.negative,
+                sub_magnitude(&self.magnitude, &other.magnitude),
+            ),
+        }
+    }
+}
+
+impl std::ops::Sub for &BigInt {
+    type Output = BigInt;
+
+    fn sub(self, other: &BigInt) -> BigInt {
+        self + &-other
+    }
+}
+
+impl std::ops::Mul for &BigInt {
+    type Output = BigInt;
+
+    fn mul(self, other: &BigInt) -> BigInt {
+        let mut product = vec![0u32; self.magnitude.len() + other.magnitude.len()];
+        for (i, &left) in self.magnitude.iter().enumerate() {
+            let mut carry = 0u64;
+            for (j, &right) in other.magnitude.iter().enumerate() {
+                let total = product[i + j] as u64 + left as u64 * right as u64 + carry;
+                product[i + j] = total as u32;
+                carry = total >> 32;
+            }
+            product[i + other.magnitude.len()] = carry as u32;
+        }
+        BigInt::from_parts(self.negative != other.negative, product)
+    }
+}
+
+impl fmt::Display for BigInt {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.is_zero() {
+            return f.write_str("0");
+        }
+
+        // peel off nine decimal digits at a time, least significant first
+        let mut chunks = Vec::new();
+        let mut magnitude = self.magnitude.clone();
+        while !magnitude.is_empty() {
+            chunks.push(div_small(&mut magnitude, DECIMAL_CHUNK));
+        }
+
+        if self.negative {
+            f.write_str("-")?;
+        }
+        let mut chunks = chunks.iter().rev();
+        if let Some(first) = chunks.next() {
+            write!(f, "{}", first)?;
+        }
+        for chunk in chunks {
+            write!(f, "{:09}", chunk)?;
+        }
+        Ok(())
+    }
+}
+
+fn cmp_magnitude(left: &[u32], right: &[u32]) -> Ordering {
+    left.len()
+        .cmp(&right.len())
+        .then_with(|| left.iter().rev().cmp(right.iter().rev()))
+}
+
+fn add_magnitude(left: &[u32], right: &[u32]) -> Vec<u32> {
+    let (long, short) = if left.len() >= right.len() {
+        (left, right)
+    } else {
+        (right, left)
+    };
+    let mut sum = Vec::with_capacity(long.len() + 1);
+    let mut carry = 0u64;
+    for (i, &digit) in long.iter().enumerate() {
+        let total = digit as u64 + *short.get(i).unwrap_or(&0) as u64 + carry;
+        sum.push(total as u32);
+        carry = total >> 32;
+    }
+    sum.push(carry as u32);
+    sum
+}
+
+/// `left - right`, where `left` must not be smaller than `right`
+fn sub_magnitude(left: &[u32], right: &[u32]) -> Vec<u32> {
+    let mut difference = Vec::with_capacity(left.len());
+    let mut borrow = 0i64;
+    for (i, &digit) in left.iter().enumerate() {
+        let mut total = digit as i64 - *right.get(i).unwrap_or(&0) as i64 - borrow;
+        borrow = 0;
+        if total < 0 {
+            total += BASE as i64;
+            borrow = 1;
+        }
+        difference.push(total as u32);
+    }
+    difference
+}
+
+/// `magnitude * factor + addend`, in place
+fn mul_small_add(magnitude: &mut Vec<u32>, factor: u32, addend: u32) {
+    let mut carry = addend as u64;
+    for digit in magnitude.iter_mut() {
+        let total = *digit as u64 * factor as u64 + carry;
+        *digit = total as u32;
+        carry = total >> 32;
+    }
+    if carry > 0 {
+        magnitude.push(carry as u32);
+    }
+}
+
+/// divide in place, returning the remainder
+fn div_small(magnitude: &mut Vec<u32>, divisor: u32) -> u32 {
+    let mut remainder = 0u64;
+    for digit in magnitude.iter_mut().rev() {
+        let current = (remainder << 32) | *digit as u64;
+        *digit = (current / divisor as u64) as u32;
+        remainder = current % divisor as u64;
+    }
+    while magnitude.last() == Some(&0) {
+        magnitude.pop();
+    }
+    remainder as u32
+}
+
+/// truncated division of magnitudes, using shift-and-subtract long division one bit at a time
+fn div_rem_magnitude(dividend: &[u32], divisor: &[u32]) -> (Vec<u32>, Vec<u32>) {
+    if let [divisor] = divisor {
+        let mut quotient = dividend.to_vec();
+        let remainder = div_small(&mut quotient, *divisor);
+        return (quotient, vec![remainder]);
+    }
+
+    let mut quotient = vec![0u32; dividend.len()];
+    let mut remainder: Vec<u32> = Vec::new();
+    for i in (0..dividend.len() * 32).rev() {
+        // remainder = remainder * 2 + next bit of the dividend
+        let bit = (dividend[i / 32] >> (i % 32)) & 1;
+        mul_small_add(&mut remainder, 2, bit);
+        if cmp_magnitude(&remainder, divisor) != Ordering::Less {
+            remainder = sub_magnitude(&remainder, divisor);
+            while remainder.last() == Some(&0) {
+                remainder.pop();
+            }
+            quotient[i / 32] |= 1 << (i % 32);
+        }
+    }
+    (quotient, remainder)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn big(text: &str) -> BigInt {
+        BigInt::parse(text).unwrap()
+    }
+
+    #[test]
+    fn test_parse_and_display() {
+        for text in ["0", "7", "-42", "4294967296", "-9223372036854775809"] {
+            assert_eq!(big(text).to_string(), text);
+        }
+        let factorial_30 = "265252859812191058636308480000000";
+        assert_eq!(big(factorial_30).to_string(), factorial_30);
+        assert_eq!(big("-0"), BigInt::zero());
+        assert_eq!(big("000123").to_string(), "123");
+        assert_eq!(BigInt::parse("12a"), None);
+        assert_eq!(BigInt::parse(""), None);
+    }
+
+    #[test]
+    fn test_arithmetic() {
+        let a = big("123456789012345678901234567890");
+        let b = big("-987654321098765432109876543210");
+        assert_eq!((&a + &b).to_string(), "-864197532086419753208641975320");
+        assert_eq!((&a - &b).to_string(), "1111111110111111111011111111100");
+        assert_eq!(
+            (&a * &b).to_string(),
+            "-121932631137021795226185032733622923332237463801111263526900"
+        );
+        assert_eq!(
+            big("2").pow(100).to_string(),
+            "1267650600228229401496703205376"
+        );
+    }
+
+    #[test]
+    fn test_div_mod() {
+        let div_mod = |a: &str, b: &str| {
+            let (q, r) = big(a).div_mod(&big(b)).unwrap();
+            (q.to_string(), r.to_string())
+        };
+        assert_eq!(div_mod("7", "2"), ("3".to_string(), "1".to_string()));
+        assert_eq!(div_mod("-7", "2"), ("-4".to_string(), "1".to_string()));
+        assert_eq!(div_mod("7", "-2"), ("-4".to_string(), "-1".to_string()));
+        assert_eq!(
+            div_mod("1267650600228229401496703205377", "18446744073709551616"),
+            ("68719476736".to_string(), "1".to_string())
+        );
+        assert_eq!(big("1").div_mod(&BigInt::zero()), None);
+    }
+
+    #[test]
+    fn test_conversions() {
+        assert_eq!(BigInt::from(i64::MIN).to_i64(), Some(i64::MIN));
+        assert_eq!(BigInt::from(i64::MAX).to_i64(), Some(i64::MAX));
+        assert_eq!(big("9223372036854775808").to_i64(), None);
+        assert_eq!(big("-9223372036854775809").to_i64(), None);
+        assert_eq!(big("18446744073709551616").to_f64(), 18446744073709551616.0);
+        assert_eq!(BigInt::from_f64(1e20), Some(big("100000000000000000000")));
+        assert_eq!(BigInt::from_f64(-3.0), Some(big("-3")));
+        assert_eq!(BigInt::from_f64(0.5), None);
+        assert_eq!(BigInt::zero().bits(), 0);
+        assert_eq!(BigInt::from(-5).bits(), 3);
+        assert_eq!(big("18446744073709551616").bits(), 65);
+        assert!(big("-1") < big("0") && big("0") < big("18446744073709551616"));
+    }
+}
@@ -0,0 +1,351 @@
+use crate::interpret::RuntimeError;
+use crate::lex::LexError;
+use crate::parse::ParseErr;
+use crate::resolve::ResolveErr;
+use crate::token::{Range, Source, Token, TokenKind};
+use std::fmt;
+use std::rc::Rc;
+
+/// an error pointing at a span of source code, rendered with the offending line and the span
+/// underlined:
+///
+/// ```text
+/// error: Expect ';' after value.
+///  --> script.lox:1:9
+///   |
+/// 1 | print 1 2;
+///   |         ^ found '2'
+/// ```
+pub struct Diagnostic {
+    /// stable identifier for the kind of error, for tooling to match on instead of the message,
+    /// e.g. `parse-expect-semicolon`. it starts with the stage that found the error
+    pub code: &'static str,
+    pub message: String,
+    pub source: Rc<Source>,
+    pub primary: Label,
+    pub secondary: Vec<Label>,
+    pub notes: Vec<String>,
+}
+
+pub struct Label {
+    pub span: Range,
+    /// may be empty, in which case only the underline is shown
+    pub message: String,
+}
+
+impl Diagnostic {
+    pub fn new(code: &'static str, source: Rc<Source>, span: Range, message: String) -> Self {
+        Self {
+            code,
+            message,
+            source,
+            primary: Label {
+                span,
+                message: String::new(),
+            },
+            secondary: Vec::new(),
+            notes: Vec::new(),
+        }
+    }
+
+    pub fn at(code: &'static str, token: &Token, message: String) -> Self {
+        Self::new(code, Rc::clone(&token.source), token.span.clone(), message)
+    }
+
+    /// set the message shown next to the primary underline
+    pub fn with_primary_label(mut self, message: String) -> Self {
+        self.primary.message = message;
+        self
+    }
+
+    pub fn with_label(mut self, span: Range, message: String) -> Self {
+        self.secondary.push(Label { span, message });
+        self
+    }
+
+    pub fn with_note(mut self, note: String) -> Self {
+        self.notes.push(note);
+        self
+    }
+
+    /// 1-based line and column of the start of the primary span
+    pub fn position(&self) -> (usize, usize) {
+        self.source.position(self.primary.span.0)
+    }
+
+    /// render as a single line of JSON. columns are 1-based and the end column is exclusive.
+    /// `label` is the primary label's message, which may be empty
+    pub fn to_json(&self) -> String {
+        let (line, start_column) = self.position();
+        let (end_line, end_column) = self.source.position(self.primary.span.1);
+        let labels: Vec<String> = self
+            .secondary
+            .iter()
+            .map(|label| {
+                let (line, start_column) = self.source.position(label.span.0);
+                let (end_line, end_column) = self.source.position(label.span.1);
+                format!(
+                    r#"{{"message":{},"line":{},"start_column":{},"end_line":{},"end_column":{}}}"#,
+                    json_string(&label.message),
+                    line,
+                    start_column,
+                    end_line,
+                    end_column
+                )
+            })
+            .collect();
+        let notes: Vec<String> = self.notes.iter().map(|note| json_string(note)).collect();
+
+        format!(
+            r#"{{"severity":"error","code":{},"message":{},"file":{},"line":{},"start_column":{},"end_line":{},"end_column":{},"label":{},"labels":[{}],"notes":[{}]}}"#,
+            json_string(self.code),
+            json_string(&self.message),
+            json_string(self.source.name()),
+            line,
+            start_column,
+            end_line,
+            end_column,
+            json_string(&self.primary.message),
+            labels.join(","),
+            notes.join(",")
+        )
+    }
+
+    fn render_label(
+        &self,
+        f: &mut fmt::Formatter<'_>,
+        label: &Label,
+        underline: char,
+        gutter: usize,
+    ) -> fmt::Result {
+        let (line, _) = self.source.position(label.span.0);
+        let bounds = self.source.line_bounds(label.span.0);
+        let text: String = self.source.range(&bounds).iter().collect();
+
+        // keep tabs so the underline lines up with the text above it
+        let padding: String = self
+            .source
+            .range(&Range(bounds.0, label.span.0.min(bounds.1)))
+            .iter()
+            .map(|&c| if c == '\t' { '\t' } else { ' ' })
+            .collect();
+        // spans running past the end of the line are cut short; empty spans still get one mark
+        let width = label
+            .span
+            .1
+            .min(bounds.1)
+            .saturating_sub(label.span.0)
+            .max(1);
+        let marks: String = std::iter::repeat_n(underline, width).collect();
+
+        writeln!(f, "{:>gutter$} |", "", gutter = gutter)?;
+        writeln!(f, "{:>gutter$} | {}", line, text, gutter = gutter)?;
+        write!(f, "{:>gutter$} | {}{}", "", padding, marks, gutter = gutter)?;
+        if !label.message.is_empty() {
+            write!(f, " {}", label.message)?;
+        }
+        writeln!(f)
+    }
+}
+
+impl fmt::Display for Diagnostic {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let (line, column) = self.position();
+        let gutter = std::iter::once(&self.primary)
+            .chain(&self.secondary)
+            .map(|label| self.source.position(label.span.0).0.to_string().len())
+            .max()
+            .unwrap_or(1);
+
+        writeln!(f, "error[{}]: {}", self.code, self.message)?;
+        writeln!(
+            f,
+            "{:>gutter$}--> {}:{}:{}",
+            "",
+            self.source.name(),
+            line,
+            column,
+            gutter = gutter
+        )?;
+        self.render_label(f, &self.primary, '^', gutter)?;
+        for label in &self.secondary {
+            self.render_label(f, label, '-', gutter)?;
+        }
+        for note in &self.notes {
+            writeln!(f, "{:>gutter$} = note: {}", "", note, gutter = gutter)?;
+        }
+        Ok(())
+    }
+}
+
+impl From<LexError> for Diagnostic {
+    fn from(error: LexError) -> Self {
+        Diagnostic::new(error.code, error.source, error.span, error.message)
+    }
+}
+
+impl From<ParseErr> for Diagnostic {
+    fn from(error: ParseErr) -> Self {
+        let found = match error.token.kind {
+            TokenKind::Eof => "found end of file".to_string(),
+            _ => format!("found '{}'", error.token.name()),
+        };
+        Diagnostic::at(error.code, &error.token, error.message).with_primary_label(found)
+    }
+}
+
+impl From<ResolveErr> for Diagnostic {
+    fn from(error: ResolveErr) -> Self {
+        let diagnostic = Diagnostic::at(error.code, &error.token, error.message);
+        match error.previous {
+            Some(previous) => diagnostic
+                .with_label(previous.span, "first declared here".to_string())
+                .with_note("variables can only be shadowed from a nested scope".to_string()),
+            None => diagnostic,
+        }
+    }
+}
+
+impl From<RuntimeError> for Diagnostic {
+    fn from(error: RuntimeError) -> Self {
+        Diagnostic::at(error.code, &error.token, error.message)
+    }
+}
+
+/// quote and escape a string for JSON output
+fn json_string(text: &str) -> String {
+    let mut quoted = String::with_capacity(text.len() + 2);
+    quoted.push('"');
+    for c in text.chars() {
+        match c {
+            '"' => quoted.push_str("\\\""),
+            '\\' => quoted.push_str("\\\\"),
+            '\n' => quoted.push_str("\\n"),
+            '\r' => quoted.push_str("\\r"),
+            '\t' => quoted.push_str("\\t"),
+            c if (c as u32) < 0x20 => quoted.push_str(&format!("\\u{:04x}", c as u32)),
+            c => quoted.push(c),
+        }
+    }
+    quoted.push('"');
+    quoted
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::interpret::Interpreter;
+    use crate::lex::lex;
+    use crate::parse::parse;
+    use crate::resolve::resolve;
+
+    fn render(source: &str) -> String {
+        let (_, mut errors) = lex("test.lox".to_string(), source);
+        Diagnostic::from(errors.remove(0)).to_string()
+    }
+
+    #[test]
+    fn test_render() {
+        assert_eq!(
+            render("var a = 1;\nprint a @ 2;"),
+            "error[lex-unexpected-character]: Unexpected character '@'.\n\
+             \x20--> test.lox:2:9\n\
+             \x20 |\n\
+             2 | print a @ 2;\n\
+             \x20 |         ^\n",
+        );
+    }
+
+    /// the code of the first error found in a program, from whichever stage found it
+    fn code(source: &str) -> &'static str {
+        let (tokens, mut errors) = lex("test.lox".to_string(), source);
+        if !errors.is_empty() {
+            return Diagnostic::from(errors.remove(0)).code;
+        }
+        let (statements, mut errors) = parse(tokens);
+        if !errors.is_empty() {
+            return Diagnostic::from(errors.remove(0)).code;
+        }
+        if let Err(mut errors) = resolve(&statements) {
+            return Diagnostic::from(errors.remove(0)).code;
+        }
+        let error = Interpreter::new()
+            .interpret(statements)
+            .expect_err("expected an error");
+        Diagnostic::from(error).code
+    }
+
+    #[test]
+    fn test_codes() {
+        let cases = [
+            ("print 1 @ 2;", "lex-unexpected-character"),
+            ("print \"abc", "lex-unterminated-string"),
+            ("print 1 2;", "parse-expect-semicolon"),
+            ("print (1;", "parse-expect-right-paren"),
+            ("1 = 2;", "parse-invalid-assignment-target"),
+            ("return 1;", "resolve-return-outside-function"),
+            ("{ var a = 1; var a = 2; }", "resolve-redeclared-variable"),
+            ("print a;", "runtime-undefined-variable"),
+            ("print 1 / 0 + nil;", "runtime-operand-type"),
+            ("print 1 % 0;", "runtime-division-by-zero"),
+            ("print sqrt(nil);", "runtime-argument-type"),
+        ];
+        for (source, expected) in cases {
+            assert_eq!(code(source), expected, "{}", source);
+        }
+    }
+
+    #[test]
+    fn test_render_labels_and_notes() {
+        let source = Rc::new(Source::new(
+            "test.lox".to_string(),
+            "\tvar abc;\n\tvar abc;".chars().collect(),
+        ));
+        let diagnostic =
+            Diagnostic::new("test", Rc::clone(&source), Range(15, 18), "bad".to_string())
+                .with_primary_label("here".to_string())
+                .with_label(Range(5, 8), "and here".to_string())
+                .with_note("a note".to_string());
+        assert_eq!(
+            diagnostic.to_string(),
+            "error[test]: bad\n\
+             \x20--> test.lox:2:6\n\
+             \x20 |\n\
+             2 | \tvar abc;\n\
+             \x20 | \t    ^^^ here\n\
+             \x20 |\n\
+             1 | \tvar abc;\n\
+             \x20 | \t    --- and here\n\
+             \x20 = note: a note\n",
+        );
+    }
+
+    #[test]
+    fn test_json() {
+        let source = Rc::new(Source::new(
+            "dir\\test.lox".to_string(),
+            "print \"a\" - 1;".chars().collect(),
+        ));
+        let diagnostic = Diagnostic::new(
+            "runtime",
+            source,
+            Range(6, 9),
+            "bad \"operand\"".to_string(),
+        )
+        .with_note("tab\there".to_string());
+        assert_eq!(
+            diagnostic.to_json(),
+            r#"{"severity":"error","code":"runtime","message":"bad \"operand\"","file":"dir\\test.lox","line":1,"start_column":7,"end_line":1,"end_column":10,"label":"","labels":[],"notes":["tab\there"]}"#,
+        );
+    }
+
+    #[test]
+    fn test_json_labels() {
+        let (tokens, _) = lex("test.lox".to_string(), "print 1 2;");
+        let (_, mut errors) = parse(tokens);
+        assert_eq!(
+            Diagnostic::from(errors.remove(0)).to_json(),
+            r#"{"severity":"error","code":"parse-expect-semicolon","message":"Expect ';' after value.","file":"test.lox","line":1,"start_column":9,"end_line":1,"end_column":10,"label":"found '2'","labels":[],"notes":[]}"#,
+        );
+    }
+}
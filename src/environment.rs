@@ -1,66 +1,95 @@
 use crate::token::Literal;
 
+use std::cell::RefCell;
 use std::collections::HashMap;
+use std::rc::Rc;
 
+/// a single scope. scopes are shared (and kept alive) by every closure that captures them, so
+/// each one points at its enclosing scope rather than living on a stack
 pub struct Environment {
-    scopes: Vec<HashMap<String, Option<Literal>>>,
+    values: HashMap<String, Literal>,
+    enclosing: Option<Rc<RefCell<Environment>>>,
 }
 
 impl Environment {
-    pub fn new() -> Self {
-        let top_level_scope = HashMap::new();
-        Self {
-            scopes: vec![top_level_scope],
-        }
+    pub fn new() -> Rc<RefCell<Self>> {
+        Rc::new(RefCell::new(Self {
+            values: HashMap::new(),
+            enclosing: None,
+        }))
     }
 
-    pub fn push(&mut self) {
-        self.scopes.push(HashMap::new());
+    pub fn with_enclosing(enclosing: &Rc<RefCell<Environment>>) -> Rc<RefCell<Self>> {
+        Rc::new(RefCell::new(Self {
+            values: HashMap::new(),
+            enclosing: Some(Rc::clone(enclosing)),
+        }))
     }
 
-    pub fn pop(&mut self) {
-        if self.scopes.len() > 1 {
-            self.scopes.pop();
-        }
-    }
-
-    pub fn define(&mut self, name: String, value: Option<Literal>) {
-        self.scopes
-            .last_mut()
-            .expect("always at least one scope")
-            .insert(name, value);
+    pub fn define(&mut self, name: String, value: Literal) {
+        self.values.insert(name, value);
     }
 
     pub fn assign(&mut self, name: String, value: Literal) -> Result<(), ()> {
-        debug_assert!(
-            self.scopes.len() >= 1,
-            "there should always be at least one scope"
-        );
+        if let Some(slot) = self.values.get_mut(&name) {
+            *slot = value;
+            return Ok(());
+        }
+
+        match &self.enclosing {
+            Some(enclosing) => enclosing.borrow_mut().assign(name, value),
+            None => Err(()),
+        }
+    }
 
-        for scope in self.scopes.iter_mut().rev() {
-            if scope.contains_key(&name) {
-                scope.insert(name, Some(value));
-                return Ok(());
-            }
+    pub fn get(&self, name: &str) -> Option<Literal> {
+        if let Some(val) = self.values.get(name) {
+            return Some(val.clone());
         }
 
-        Err(())
+        self.enclosing
+            .as_ref()
+            .and_then(|enclosing| enclosing.borrow().get(name))
     }
 
-    pub fn get(&mut self, name: &str) -> Option<Literal> {
-        debug_assert!(
-            self.scopes.len() >= 1,
-            "there should always be at least one scope"
-        );
+    /// get a variable from the scope `distance` steps out, as computed by the resolver
+    pub fn get_at(
+        environment: &Rc<RefCell<Environment>>,
+        distance: usize,
+        name: &str,
+    ) -> Option<Literal> {
+        Environment::ancestor(environment, distance)
+            .borrow()
+            .values
+            .get(name)
+            .cloned()
+    }
 
-        for scope in self.scopes.iter_mut().rev() {
-            let val = scope.get(name);
-            if let Some(val) = val {
-                // unwrap twice since it may exist but not have been initialized
-                return val.clone();
-            }
-        }
+    pub fn assign_at(
+        environment: &Rc<RefCell<Environment>>,
+        distance: usize,
+        name: String,
+        value: Literal,
+    ) {
+        Environment::ancestor(environment, distance)
+            .borrow_mut()
+            .values
+            .insert(name, value);
+    }
 
-        None
+    fn ancestor(
+        environment: &Rc<RefCell<Environment>>,
+        distance: usize,
+    ) -> Rc<RefCell<Environment>> {
+        let mut environment = Rc::clone(environment);
+        for _ in 0..distance {
+            let enclosing = environment
+                .borrow()
+                .enclosing
+                .clone()
+                .expect("resolver should never produce a distance past the global scope");
+            environment = enclosing;
+        }
+        environment
     }
 }
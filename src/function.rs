@@ -0,0 +1,56 @@
+use crate::environment::Environment;
+use crate::stmt::FunctionDecl;
+use crate::token::Literal;
+use std::cell::RefCell;
+use std::fmt;
+use std::rc::Rc;
+
+pub struct LoxFunction {
+    pub declaration: Rc<FunctionDecl>,
+    /// the scope the function was declared in, kept alive for as long as the function is
+    pub closure: Rc<RefCell<Environment>>,
+    /// initializers always return `this`, no matter how they exit
+    pub is_initializer: bool,
+}
+
+impl LoxFunction {
+    pub fn new(
+        declaration: Rc<FunctionDecl>,
+        closure: Rc<RefCell<Environment>>,
+        is_initializer: bool,
+    ) -> Self {
+        Self {
+            declaration,
+            closure,
+            is_initializer,
+        }
+    }
+
+    /// create a copy of this method with `this` bound to the given instance
+    pub fn bind(&self, instance: Literal) -> LoxFunction {
+        let scope = Environment::with_enclosing(&self.closure);
+        scope.borrow_mut().define("this".to_string(), instance);
+        LoxFunction::new(Rc::clone(&self.declaration), scope, self.is_initializer)
+    }
+
+    pub fn arity(&self) -> usize {
+        self.declaration.params.len()
+    }
+
+    pub fn name(&self) -> String {
+        self.declaration.name.name()
+    }
+}
+
+/// functions are only ever equal to themselves
+impl PartialEq for LoxFunction {
+    fn eq(&self, other: &LoxFunction) -> bool {
+        std::ptr::eq(self, other)
+    }
+}
+
+impl fmt::Debug for LoxFunction {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "<fn {}>", self.name())
+    }
+}
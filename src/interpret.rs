@@ -1,155 +1,553 @@
+use crate::bigint::BigInt;
+use crate::class::{LoxClass, LoxInstance};
 use crate::environment::Environment;
-use crate::stmt::{Expr, Stmt};
+use crate::function::LoxFunction;
+use crate::map::LoxMap;
+use crate::native::{self, NativeFunction};
+use crate::stmt::{Depth, Expr, Stmt};
 use crate::token::{Literal, Token, TokenKind};
+use std::cell::RefCell;
+use std::cmp::Ordering;
+use std::collections::HashMap;
+use std::convert::TryFrom;
+use std::fmt;
+use std::rc::Rc;
 
-pub fn interpret(statements: Vec<Stmt>, environment: Option<Environment>) -> Environment {
-    Interpreter {
-        environment: environment.unwrap_or(Environment::new()),
+#[derive(Debug)]
+pub struct RuntimeError {
+    /// stable identifier for the kind of error, see `Diagnostic::code`
+    pub code: &'static str,
+    pub token: Token,
+    pub message: String,
+}
+
+impl RuntimeError {
+    fn new(code: &'static str, token: &Token, message: String) -> Self {
+        Self {
+            code,
+            token: token.clone(),
+            message,
+        }
+    }
+}
+
+impl fmt::Display for RuntimeError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "[line {}, column {}] Error: {}",
+            self.token.line, self.token.column, self.message
+        )
+    }
+}
+
+/// how deep calls can nest before a program is stopped with a stack overflow error, rather
+/// than overflowing the interpreter's own stack
+const MAX_CALL_DEPTH: usize = 1000;
+
+/// enough stack for the interpreter to reach `MAX_CALL_DEPTH`, even in debug builds, which use
+/// around ten times as much per call as release builds
+pub const STACK_SIZE: usize = 256 * 1024 * 1024;
+
+/// runs programs, keeping their globals around so that e.g. each line of the REPL can use
+/// what the lines before it defined
+pub struct Interpreter {
+    environment: Rc<RefCell<Environment>>,
+    globals: Rc<RefCell<Environment>>,
+    /// the closing `)` of the native function call in progress, which its errors point at
+    native_call: Option<Token>,
+    /// the number of functions currently being called
+    call_depth: usize,
+}
+
+impl Default for Interpreter {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// what should happen after a statement has been executed
+enum Flow {
+    Next,
+    /// leave the loop with the given label, or the innermost loop
+    Break(Option<String>),
+    Continue(Option<String>),
+    Return(Literal),
+}
+
+/// whether a `break` or `continue` aimed at `target` stops at the loop with the given label
+fn is_target(label: &Option<Token>, target: &Option<String>) -> bool {
+    match target {
+        Some(target) => label.as_ref().is_some_and(|label| label.name() == *target),
+        None => true,
     }
-    .interpret(statements)
 }
 
-struct Interpreter {
-    environment: Environment,
+/// how far a `for (x in ...)` loop has got through what it's looping over
+enum Iteration {
+    /// lists are indexed afresh on every step, so the body can change the list it's looping over
+    List(Rc<RefCell<Vec<Literal>>>, usize),
+    /// map keys and string characters are collected up front
+    Items(std::vec::IntoIter<Literal>),
+    Range(Box<dyn Iterator<Item = i64>>),
+    /// the bound `next` method of an iterator object, which returns nil once it's done
+    Next(Literal),
 }
 
 impl Interpreter {
-    fn interpret(mut self, statements: Vec<Stmt>) -> Environment {
+    /// an interpreter whose global scope holds the prelude
+    pub fn new() -> Self {
+        let globals = Environment::new();
+        for (name, value) in native::prelude() {
+            globals.borrow_mut().define(name.to_string(), value);
+        }
+        Self {
+            environment: Rc::clone(&globals),
+            globals,
+            native_call: None,
+            call_depth: 0,
+        }
+    }
+
+    /// an error in the native function being called
+    pub fn native_error(&self, code: &'static str, message: String) -> RuntimeError {
+        let paren = self
+            .native_call
+            .as_ref()
+            .expect("only native functions report errors this way");
+        RuntimeError::new(code, paren, message)
+    }
+
+    /// run a program, stopping at the first runtime error
+    pub fn interpret(&mut self, statements: Vec<Stmt>) -> Result<(), RuntimeError> {
         for statement in statements {
-            self.interpret_statement(&statement);
+            self.interpret_statement(&statement)?;
         }
 
-        self.environment
+        Ok(())
+    }
+
+    fn look_up_variable(&self, name: &Token, depth: &Depth) -> Result<Literal, RuntimeError> {
+        match depth.get() {
+            Some(distance) => Environment::get_at(&self.environment, distance, &name.name()),
+            None => self.globals.borrow().get(&name.name()),
+        }
+        .ok_or_else(|| {
+            RuntimeError::new(
+                "runtime-undefined-variable",
+                name,
+                format!("Undefined variable '{}'.", name.name()),
+            )
+        })
     }
 
-    fn interpret_statement(&mut self, statement: &Stmt) {
+    fn interpret_statement(&mut self, statement: &Stmt) -> Result<Flow, RuntimeError> {
         match statement {
             Stmt::Expression { expr } => {
-                self.interpret_expression(expr);
+                self.interpret_expression(expr)?;
             }
             Stmt::Print { expr } => {
-                println!("{}", self.interpret_expression(expr));
+                println!("{}", self.interpret_expression(expr)?);
             }
-            Stmt::Var { name, initializer } => {
-                if let Some(initializer) = initializer {
-                    let value = self.interpret_expression(initializer);
-                    self.environment.define(name.name(), Some(value));
-                } else {
-                    self.environment.define(name.name(), None);
-                }
+            Stmt::Var {
+                name, initializer, ..
+            } => {
+                let value = match initializer {
+                    Some(initializer) => self.interpret_expression(initializer)?,
+                    None => Literal::Nil,
+                };
+                self.environment.borrow_mut().define(name.name(), value);
             }
             Stmt::Block { statements } => {
-                self.environment.push();
-                for statement in statements {
-                    self.interpret_statement(statement);
-                }
-                self.environment.pop();
+                let scope = Environment::with_enclosing(&self.environment);
+                return self.interpret_block(statements, scope);
             }
             Stmt::If {
                 condition,
                 then_branch,
                 else_branch,
             } => {
-                if self.interpret_expression(condition).is_truthy() {
-                    self.interpret_statement(then_branch);
+                if self.interpret_expression(condition)?.is_truthy() {
+                    return self.interpret_statement(then_branch);
                 } else if let Some(else_branch) = else_branch {
-                    self.interpret_statement(else_branch);
+                    return self.interpret_statement(else_branch);
                 }
             }
-            Stmt::While { body, condition } => {
-                while self.interpret_expression(condition.clone()).is_truthy() {
-                    self.interpret_statement(body);
+            Stmt::While {
+                body,
+                condition,
+                increment,
+                label,
+            } => {
+                while self.interpret_expression(condition)?.is_truthy() {
+                    match self.interpret_statement(body)? {
+                        Flow::Next => {}
+                        Flow::Continue(target) if is_target(label, &target) => {}
+                        Flow::Break(target) if is_target(label, &target) => break,
+                        // aimed at an outer loop, or returning from the function
+                        flow => return Ok(flow),
+                    }
+                    if let Some(increment) = increment {
+                        self.interpret_expression(increment)?;
+                    }
                 }
             }
+            Stmt::ForIn {
+                name,
+                keyword,
+                iterable,
+                body,
+                label,
+            } => return self.for_in(name, keyword, iterable, body, label),
+            Stmt::Break { label, .. } => return Ok(Flow::Break(label.as_ref().map(Token::name))),
+            Stmt::Continue { label, .. } => {
+                return Ok(Flow::Continue(label.as_ref().map(Token::name)))
+            }
+            Stmt::Function { decl } => {
+                let function =
+                    LoxFunction::new(Rc::clone(decl), Rc::clone(&self.environment), false);
+                self.environment
+                    .borrow_mut()
+                    .define(decl.name.name(), Literal::Function(Rc::new(function)));
+            }
+            Stmt::Return { value, .. } => {
+                let value = match value {
+                    Some(value) => self.interpret_expression(value)?,
+                    None => Literal::Nil,
+                };
+                return Ok(Flow::Return(value));
+            }
+            Stmt::Class {
+                name,
+                superclass,
+                methods,
+                ..
+            } => {
+                let superclass = match superclass {
+                    Some(expr) => match self.interpret_expression(expr)? {
+                        Literal::Class(superclass) => Some(superclass),
+                        _ => {
+                            let token = match expr {
+                                Expr::Variable { name, .. } => name,
+                                _ => name,
+                            };
+                            return Err(RuntimeError::new(
+                                "runtime-superclass-not-class",
+                                token,
+                                "Superclass must be a class.".to_string(),
+                            ));
+                        }
+                    },
+                    None => None,
+                };
+
+                // methods of a subclass close over a scope where `super` is its superclass
+                let closure = match &superclass {
+                    Some(superclass) => {
+                        let scope = Environment::with_enclosing(&self.environment);
+                        scope
+                            .borrow_mut()
+                            .define("super".to_string(), Literal::Class(Rc::clone(superclass)));
+                        scope
+                    }
+                    None => Rc::clone(&self.environment),
+                };
+
+                let methods: HashMap<_, _> = methods
+                    .iter()
+                    .map(|decl| {
+                        let method_name = decl.name.name();
+                        let function = LoxFunction::new(
+                            Rc::clone(decl),
+                            Rc::clone(&closure),
+                            method_name == "init",
+                        );
+                        (method_name, Rc::new(function))
+                    })
+                    .collect();
+                let class = LoxClass::new(name.name(), superclass, methods);
+                self.environment
+                    .borrow_mut()
+                    .define(name.name(), Literal::Class(Rc::new(class)));
+            }
         }
+
+        Ok(Flow::Next)
     }
 
-    fn interpret_expression(&mut self, expr: &Expr) -> Literal {
-        match expr {
-            Expr::Literal { val } => val.clone(),
-            Expr::Grouping { expr } => self.interpret_expression(expr),
-            Expr::Variable { name } => self.environment.get(&name.name()).unwrap_or(Literal::Nil),
-            Expr::Unary { operator, right } => {
-                let right = self.interpret_expression(right);
-                match operator.kind {
-                    TokenKind::Bang => Literal::Bool(!right.is_truthy()),
-                    TokenKind::Minus => {
-                        match right {
-                            Literal::Number(num) => Literal::Number(-num),
-                            _ => Literal::Nil, // TODO: fail here
-                        }
+    /// run statements in the given scope, stopping early if one of them returns or fails
+    fn interpret_block(
+        &mut self,
+        statements: &[Stmt],
+        scope: Rc<RefCell<Environment>>,
+    ) -> Result<Flow, RuntimeError> {
+        let previous = std::mem::replace(&mut self.environment, scope);
+        let mut result = Ok(Flow::Next);
+        for statement in statements {
+            result = self.interpret_statement(statement);
+            if !matches!(result, Ok(Flow::Next)) {
+                break;
+            }
+        }
+        self.environment = previous;
+        result
+    }
+
+    fn for_in(
+        &mut self,
+        name: &Token,
+        keyword: &Token,
+        iterable: &Expr,
+        body: &Stmt,
+        label: &Option<Token>,
+    ) -> Result<Flow, RuntimeError> {
+        let iterable = self.interpret_expression(iterable)?;
+        let mut iteration = self.iterate(keyword, iterable)?;
+        while let Some(item) = self.step(keyword, &mut iteration)? {
+            // a new variable for each iteration, so closures made in the body each see their own
+            let scope = Environment::with_enclosing(&self.environment);
+            scope.borrow_mut().define(name.name(), item);
+            match self.interpret_block(std::slice::from_ref(body), scope)? {
+                Flow::Next => {}
+                Flow::Continue(target) if is_target(label, &target) => {}
+                Flow::Break(target) if is_target(label, &target) => break,
+                flow => return Ok(flow),
+            }
+        }
+
+        Ok(Flow::Next)
+    }
+
+    /// start looping over a value. instances are looped over with whatever their `iter` method
+    /// returns, or failing that with their own `next` method
+    fn iterate(&mut self, keyword: &Token, iterable: Literal) -> Result<Iteration, RuntimeError> {
+        Ok(match iterable {
+            Literal::List(list) => Iteration::List(list, 0),
+            Literal::Map(map) => {
+                let keys: Vec<_> = map.borrow().iter().map(|(key, _)| key.clone()).collect();
+                Iteration::Items(keys.into_iter())
+            }
+            Literal::Str(text) => {
+                let chars: Vec<_> = text.chars().map(|c| Literal::Str(c.to_string())).collect();
+                Iteration::Items(chars.into_iter())
+            }
+            Literal::Range {
+                start,
+                end,
+                inclusive: true,
+            } => Iteration::Range(Box::new(start..=end)),
+            Literal::Range { start, end, .. } => Iteration::Range(Box::new(start..end)),
+            Literal::Instance(instance) => {
+                if let Some(iter) = protocol_method(&instance, "iter") {
+                    return match self.call(iter, keyword, Vec::new())? {
+                        Literal::Instance(iterator) => match protocol_method(&iterator, "next") {
+                            Some(next) => Ok(Iteration::Next(next)),
+                            None => Err(RuntimeError::new(
+                                "runtime-not-iterable",
+                                keyword,
+                                format!(
+                                    "The instance of '{}' returned by 'iter' has no 'next' method.",
+                                    iterator.borrow().class.name
+                                ),
+                            )),
+                        },
+                        // e.g. a list, which can't lead back here
+                        iterable => self.iterate(keyword, iterable),
+                    };
+                }
+                match protocol_method(&instance, "next") {
+                    Some(next) => Iteration::Next(next),
+                    None => {
+                        return Err(RuntimeError::new(
+                            "runtime-not-iterable",
+                            keyword,
+                            format!(
+                                "Can't loop over an instance of '{}' without an 'iter' or 'next' method.",
+                                instance.borrow().class.name
+                            ),
+                        ))
                     }
-                    _ => Literal::Nil, // TODO: fail here
                 }
             }
-            Expr::Binary {
-                left,
-                operator,
-                right,
-            } => {
-                let left = self.interpret_expression(left);
-                let right = self.interpret_expression(right);
-                match (&operator.kind, left, right) {
-                    (TokenKind::Minus, Literal::Number(left), Literal::Number(right)) => {
-                        Literal::Number(left - right)
-                    }
-                    (TokenKind::Star, Literal::Number(left), Literal::Number(right)) => {
-                        Literal::Number(left * right)
-                    }
+            _ => {
+                return Err(RuntimeError::new(
+                    "runtime-not-iterable",
+                    keyword,
+                    format!("Can't loop over a {}.", iterable.kind_name()),
+                ))
+            }
+        })
+    }
 
-                    // Plus is overloaded so we handle a few cases
-                    (TokenKind::Plus, Literal::Number(left), Literal::Number(right)) => {
-                        Literal::Number(left + right)
-                    }
-                    (TokenKind::Plus, Literal::Str(mut left), Literal::Str(right)) => {
-                        left.push_str(&right);
-                        Literal::Str(left)
-                    }
-                    (TokenKind::Plus, left, Literal::Str(right)) => {
-                        let mut left = left.to_string();
-                        left.push_str(&right);
-                        Literal::Str(left)
-                    }
-                    (TokenKind::Plus, Literal::Str(mut left), right) => {
-                        left.push_str(&right.to_string());
-                        Literal::Str(left)
-                    }
+    /// the next item of a loop, if there is one
+    fn step(
+        &mut self,
+        keyword: &Token,
+        iteration: &mut Iteration,
+    ) -> Result<Option<Literal>, RuntimeError> {
+        Ok(match iteration {
+            Iteration::List(list, index) => {
+                let item = list.borrow().get(*index).cloned();
+                *index += 1;
+                item
+            }
+            Iteration::Items(items) => items.next(),
+            Iteration::Range(range) => range.next().map(Literal::Integer),
+            Iteration::Next(next) => match self.call(next.clone(), keyword, Vec::new())? {
+                Literal::Nil => None,
+                item => Some(item),
+            },
+        })
+    }
 
-                    (TokenKind::Greater, Literal::Number(left), Literal::Number(right)) => {
-                        Literal::Bool(left > right)
-                    }
-                    (TokenKind::GreaterEqual, Literal::Number(left), Literal::Number(right)) => {
-                        Literal::Bool(left >= right)
-                    }
-                    (TokenKind::Less, Literal::Number(left), Literal::Number(right)) => {
-                        Literal::Bool(left < right)
-                    }
-                    (TokenKind::LessEqual, Literal::Number(left), Literal::Number(right)) => {
-                        Literal::Bool(left <= right)
-                    }
+    fn call(
+        &mut self,
+        callee: Literal,
+        paren: &Token,
+        arguments: Vec<Literal>,
+    ) -> Result<Literal, RuntimeError> {
+        let arity = match &callee {
+            Literal::Function(function) => function.arity(),
+            Literal::Class(class) => class.arity(),
+            Literal::Native(native) => return self.call_native(native, paren, arguments),
+            _ => {
+                return Err(RuntimeError::new(
+                    "runtime-not-callable",
+                    paren,
+                    "Can only call functions and classes.".to_string(),
+                ))
+            }
+        };
 
-                    (TokenKind::BangEqual, left, right) => Literal::Bool(left != right),
-                    (TokenKind::EqualEqual, left, right) => Literal::Bool(left == right),
+        check_arity(paren, arity, &arguments)?;
 
-                    (operator, left, right) => {
-                        println!(
-                            "warning: operator {:?} cannot be applied to values of type {} and {}",
-                            operator,
-                            left.kind_name(),
-                            right.kind_name()
-                        );
-                        Literal::Nil
-                    }
+        match callee {
+            Literal::Function(function) => self.call_function(&function, paren, arguments),
+            Literal::Class(class) => {
+                let instance =
+                    Literal::Instance(Rc::new(RefCell::new(LoxInstance::new(Rc::clone(&class)))));
+                if let Some(init) = class.find_method("init") {
+                    self.call_function(&init.bind(instance.clone()), paren, arguments)?;
                 }
+                Ok(instance)
             }
-            Expr::Assign { name, value } => {
-                let value = self.interpret_expression(value);
-                if let Err(_) = self
-                    .environment
-                    // TODO: every value is stored twice!!!!! oof
-                    .assign(name.name(), value.clone())
-                {
-                    println!("variable {} not declared", name.name())
+            _ => unreachable!("only functions and classes are callable"),
+        }
+    }
+
+    /// count a call as started, unless there are too many calls going on already. every
+    /// successful call must be matched by decrementing `call_depth` once it's finished
+    fn enter_call(&mut self, paren: &Token) -> Result<(), RuntimeError> {
+        if self.call_depth == MAX_CALL_DEPTH {
+            return Err(RuntimeError::new(
+                "runtime-stack-overflow",
+                paren,
+                "Stack overflow.".to_string(),
+            ));
+        }
+        self.call_depth += 1;
+        Ok(())
+    }
+
+    fn call_native(
+        &mut self,
+        native: &NativeFunction,
+        paren: &Token,
+        arguments: Vec<Literal>,
+    ) -> Result<Literal, RuntimeError> {
+        if let Some(arity) = native.arity {
+            check_arity(paren, arity, &arguments)?;
+        }
+        self.enter_call(paren)?;
+        let outer = self.native_call.replace(paren.clone());
+        let result = (native.function)(self, arguments);
+        self.native_call = outer;
+        self.call_depth -= 1;
+        result
+    }
+
+    fn call_function(
+        &mut self,
+        function: &LoxFunction,
+        paren: &Token,
+        arguments: Vec<Literal>,
+    ) -> Result<Literal, RuntimeError> {
+        let scope = Environment::with_enclosing(&function.closure);
+        for (param, argument) in function.declaration.params.iter().zip(arguments) {
+            scope.borrow_mut().define(param.name(), argument);
+        }
+        self.enter_call(paren)?;
+        let flow = self.interpret_block(&function.declaration.body, scope);
+        self.call_depth -= 1;
+        let flow = flow?;
+
+        if function.is_initializer {
+            return Ok(function
+                .closure
+                .borrow()
+                .get("this")
+                .expect("initializers are always bound to an instance"));
+        }
+
+        Ok(match flow {
+            Flow::Return(value) => value,
+            // the parser doesn't allow `break` or `continue` to escape a function
+            Flow::Next | Flow::Break(_) | Flow::Continue(_) => Literal::Nil,
+        })
+    }
+
+    fn interpret_expressions(&mut self, exprs: &[Expr]) -> Result<Vec<Literal>, RuntimeError> {
+        exprs
+            .iter()
+            .map(|expr| self.interpret_expression(expr))
+            .collect()
+    }
+
+    /// the bigger kinds of expression are handled in functions of their own, so that the stack
+    /// frame of this function, which every nested expression and call goes through, stays small
+    fn interpret_expression(&mut self, expr: &Expr) -> Result<Literal, RuntimeError> {
+        Ok(match expr {
+            Expr::Literal { val } => val.clone(),
+            Expr::Interpolation { parts } => {
+                let mut text = String::new();
+                for part in parts {
+                    text.push_str(&self.interpret_expression(part)?.to_string());
+                }
+                Literal::Str(text)
+            }
+            Expr::Grouping { expr } => self.interpret_expression(expr)?,
+            Expr::Variable { name, depth } => self.look_up_variable(name, depth)?,
+            Expr::Unary { operator, right } => unary(operator, self.interpret_expression(right)?)?,
+            Expr::Binary {
+                left,
+                operator,
+                right,
+            } => {
+                let left = self.interpret_expression(left)?;
+                let right = self.interpret_expression(right)?;
+                binary(operator, left, right)?
+            }
+            Expr::Assign { name, value, depth } => {
+                let value = self.interpret_expression(value)?;
+                // TODO: every value is stored twice!!!!! oof
+                match depth.get() {
+                    Some(distance) => Environment::assign_at(
+                        &self.environment,
+                        distance,
+                        name.name(),
+                        value.clone(),
+                    ),
+                    None => self
+                        .globals
+                        .borrow_mut()
+                        .assign(name.name(), value.clone())
+                        .map_err(|_| {
+                            RuntimeError::new(
+                                "runtime-undefined-variable",
+                                name,
+                                format!("Undefined variable '{}'.", name.name()),
+                            )
+                        })?,
                 }
                 value
             }
@@ -158,23 +556,1438 @@ impl Interpreter {
                 operator,
                 right,
             } => {
-                let left = self.interpret_expression(left);
+                let left = self.interpret_expression(left)?;
                 if let Token {
                     kind: TokenKind::Or,
                     ..
                 } = operator
                 {
                     if left.is_truthy() {
-                        return left;
-                    }
-                } else {
-                    if !left.is_truthy() {
-                        return left;
+                        return Ok(left);
                     }
+                } else if !left.is_truthy() {
+                    return Ok(left);
+                }
+
+                self.interpret_expression(right)?
+            }
+            Expr::Call {
+                callee,
+                paren,
+                arguments,
+            } => self.call_expression(callee, paren, arguments)?,
+            Expr::Get { object, name } => get_property(self.interpret_expression(object)?, name)?,
+            Expr::List { elements } => {
+                Literal::List(Rc::new(RefCell::new(self.interpret_expressions(elements)?)))
+            }
+            Expr::Map { brace, entries } => self.map(brace, entries)?,
+            Expr::Index {
+                object,
+                bracket,
+                index,
+            } => self.index(object, bracket, index)?,
+            Expr::IndexSet {
+                object,
+                bracket,
+                index,
+                value,
+            } => self.index_set(object, bracket, index, value)?,
+            Expr::Slice {
+                object,
+                bracket,
+                start,
+                end,
+            } => self.slice(object, bracket, start.as_deref(), end.as_deref())?,
+            Expr::Set {
+                object,
+                name,
+                value,
+            } => match self.interpret_expression(object)? {
+                Literal::Instance(instance) => {
+                    let value = self.interpret_expression(value)?;
+                    instance.borrow_mut().set(name.name(), value.clone());
+                    value
+                }
+                _ => {
+                    return Err(RuntimeError::new(
+                        "runtime-not-an-instance",
+                        name,
+                        "Only instances have fields.".to_string(),
+                    ))
                 }
+            },
+            Expr::This { keyword, depth } => self.look_up_variable(keyword, depth)?,
+            Expr::Super { method, depth, .. } => self.super_method(method, depth)?,
+        })
+    }
+
+    fn call_expression(
+        &mut self,
+        callee: &Expr,
+        paren: &Token,
+        arguments: &[Expr],
+    ) -> Result<Literal, RuntimeError> {
+        // methods of built-in types aren't values of their own, so they're looked up and
+        // called in one go
+        let callee = match callee {
+            Expr::Get { object, name } => match self.interpret_expression(object)? {
+                Literal::List(list) => {
+                    let arguments = self.interpret_expressions(arguments)?;
+                    return call_list_method(&list, name, paren, arguments);
+                }
+                Literal::Map(map) => {
+                    let arguments = self.interpret_expressions(arguments)?;
+                    return call_map_method(&map, name, paren, arguments);
+                }
+                object => get_property(object, name)?,
+            },
+            callee => self.interpret_expression(callee)?,
+        };
+        let arguments = self.interpret_expressions(arguments)?;
+        self.call(callee, paren, arguments)
+    }
+
+    fn super_method(&mut self, method: &Token, depth: &Depth) -> Result<Literal, RuntimeError> {
+        // `this` is always bound in the scope just inside the one holding `super`
+        let distance = depth.get().expect("super is always resolved to a local");
+        let superclass = Environment::get_at(&self.environment, distance, "super");
+        let instance = Environment::get_at(&self.environment, distance - 1, "this");
+        let (superclass, instance) = match (superclass, instance) {
+            (Some(Literal::Class(superclass)), Some(instance)) => (superclass, instance),
+            _ => unreachable!("the resolver only allows super inside subclass methods"),
+        };
+        match superclass.find_method(&method.name()) {
+            Some(found) => Ok(Literal::Function(Rc::new(found.bind(instance)))),
+            None => Err(RuntimeError::new(
+                "runtime-undefined-property",
+                method,
+                format!("Undefined property '{}'.", method.name()),
+            )),
+        }
+    }
+
+    fn map(&mut self, brace: &Token, entries: &[(Expr, Expr)]) -> Result<Literal, RuntimeError> {
+        let mut map = LoxMap::default();
+        for (key, value) in entries {
+            let key = self.interpret_expression(key)?;
+            let value = self.interpret_expression(value)?;
+            map.insert(key, value).map_err(|_| nan_key(brace))?;
+        }
+        Ok(Literal::Map(Rc::new(RefCell::new(map))))
+    }
+
+    fn index(
+        &mut self,
+        object: &Expr,
+        bracket: &Token,
+        index: &Expr,
+    ) -> Result<Literal, RuntimeError> {
+        let object = self.interpret_expression(object)?;
+        let index = self.interpret_expression(index)?;
+        match object {
+            Literal::List(list) => {
+                let list = list.borrow();
+                Ok(list[list_index(bracket, &index, list.len(), false)?].clone())
+            }
+            Literal::Map(map) => map.borrow().get(&index).cloned().ok_or_else(|| {
+                RuntimeError::new(
+                    "runtime-undefined-key",
+                    bracket,
+                    format!("Undefined key '{}'.", index),
+                )
+            }),
+            _ => Err(not_indexable(bracket)),
+        }
+    }
+
+    fn index_set(
+        &mut self,
+        object: &Expr,
+        bracket: &Token,
+        index: &Expr,
+        value: &Expr,
+    ) -> Result<Literal, RuntimeError> {
+        let object = self.interpret_expression(object)?;
+        let index = self.interpret_expression(index)?;
+        let value = self.interpret_expression(value)?;
+        match object {
+            Literal::List(list) => {
+                let mut list = list.borrow_mut();
+                let index = list_index(bracket, &index, list.len(), false)?;
+                list[index] = value.clone();
+                Ok(value)
+            }
+            Literal::Map(map) => {
+                map.borrow_mut()
+                    .insert(index, value.clone())
+                    .map_err(|_| nan_key(bracket))?;
+                Ok(value)
+            }
+            _ => Err(not_indexable(bracket)),
+        }
+    }
 
-                self.interpret_expression(right)
+    fn slice(
+        &mut self,
+        object: &Expr,
+        bracket: &Token,
+        start: Option<&Expr>,
+        end: Option<&Expr>,
+    ) -> Result<Literal, RuntimeError> {
+        let object = self.interpret_expression(object)?;
+        let start = match start {
+            Some(start) => Some(self.interpret_expression(start)?),
+            None => None,
+        };
+        let end = match end {
+            Some(end) => Some(self.interpret_expression(end)?),
+            None => None,
+        };
+        let list = match object {
+            Literal::List(list) => list,
+            _ => {
+                return Err(RuntimeError::new(
+                    "runtime-not-sliceable",
+                    bracket,
+                    "Can only slice lists.".to_string(),
+                ))
             }
+        };
+
+        let list = list.borrow();
+        let start = match start {
+            Some(start) => list_index(bracket, &start, list.len(), true)?,
+            None => 0,
+        };
+        let end = match end {
+            Some(end) => list_index(bracket, &end, list.len(), true)?,
+            None => list.len(),
+        };
+        if start > end {
+            return Err(RuntimeError::new(
+                "runtime-bad-slice",
+                bracket,
+                format!("Slice starts at {} but ends before it at {}.", start, end),
+            ));
         }
+        Ok(Literal::List(Rc::new(RefCell::new(
+            list[start..end].to_vec(),
+        ))))
+    }
+}
+
+/// a method of the iterator protocol, bound to the instance. unlike property access, this
+/// ignores fields, so e.g. a linked list node's `next` field isn't mistaken for the method
+fn protocol_method(instance: &Rc<RefCell<LoxInstance>>, name: &str) -> Option<Literal> {
+    let method = instance.borrow().class.find_method(name)?;
+    let bound = method.bind(Literal::Instance(Rc::clone(instance)));
+    Some(Literal::Function(Rc::new(bound)))
+}
+
+fn check_arity(paren: &Token, arity: usize, arguments: &[Literal]) -> Result<(), RuntimeError> {
+    if arguments.len() == arity {
+        Ok(())
+    } else {
+        Err(RuntimeError::new(
+            "runtime-arity",
+            paren,
+            format!("Expected {} arguments but got {}.", arity, arguments.len()),
+        ))
+    }
+}
+
+fn get_property(object: Literal, name: &Token) -> Result<Literal, RuntimeError> {
+    match object {
+        Literal::Instance(instance) => LoxInstance::get(&instance, &name.name()).ok_or_else(|| {
+            RuntimeError::new(
+                "runtime-undefined-property",
+                name,
+                format!("Undefined property '{}'.", name.name()),
+            )
+        }),
+        Literal::List(_) => Err(RuntimeError::new(
+            "runtime-uncalled-method",
+            name,
+            format!(
+                "List methods must be called right away, e.g. 'xs.{}()'.",
+                name.name()
+            ),
+        )),
+        Literal::Map(_) => Err(RuntimeError::new(
+            "runtime-uncalled-method",
+            name,
+            format!(
+                "Map methods must be called right away, e.g. 'm.{}()'.",
+                name.name()
+            ),
+        )),
+        _ => Err(RuntimeError::new(
+            "runtime-not-an-instance",
+            name,
+            "Only instances have properties.".to_string(),
+        )),
+    }
+}
+
+/// call one of the built-in methods of lists
+fn call_list_method(
+    list: &Rc<RefCell<Vec<Literal>>>,
+    name: &Token,
+    paren: &Token,
+    mut arguments: Vec<Literal>,
+) -> Result<Literal, RuntimeError> {
+    let method = name.name();
+    let arity = match method.as_str() {
+        "len" | "pop" => 0,
+        "push" | "remove" => 1,
+        "insert" => 2,
+        _ => {
+            return Err(RuntimeError::new(
+                "runtime-undefined-property",
+                name,
+                format!("Undefined property '{}'.", method),
+            ))
+        }
+    };
+    check_arity(paren, arity, &arguments)?;
+
+    let mut list = list.borrow_mut();
+    Ok(match method.as_str() {
+        "len" => Literal::Integer(list.len() as i64),
+        "push" => {
+            list.push(arguments.remove(0));
+            Literal::Nil
+        }
+        "pop" => list.pop().ok_or_else(|| {
+            RuntimeError::new(
+                "runtime-empty-list",
+                paren,
+                "Can't pop from an empty list.".to_string(),
+            )
+        })?,
+        "insert" => {
+            let index = list_index(paren, &arguments[0], list.len(), true)?;
+            list.insert(index, arguments.remove(1));
+            Literal::Nil
+        }
+        "remove" => {
+            let index = list_index(paren, &arguments[0], list.len(), false)?;
+            list.remove(index)
+        }
+        _ => unreachable!("arity is only known for list methods"),
+    })
+}
+
+/// call one of the built-in methods of maps
+fn call_map_method(
+    map: &Rc<RefCell<LoxMap>>,
+    name: &Token,
+    paren: &Token,
+    arguments: Vec<Literal>,
+) -> Result<Literal, RuntimeError> {
+    let method = name.name();
+    let arity = match method.as_str() {
+        "len" | "keys" | "values" => 0,
+        "has" | "delete" => 1,
+        _ => {
+            return Err(RuntimeError::new(
+                "runtime-undefined-property",
+                name,
+                format!("Undefined property '{}'.", method),
+            ))
+        }
+    };
+    check_arity(paren, arity, &arguments)?;
+
+    let mut map = map.borrow_mut();
+    let list = |items: Vec<Literal>| Literal::List(Rc::new(RefCell::new(items)));
+    Ok(match method.as_str() {
+        "len" => Literal::Integer(map.iter().len() as i64),
+        "keys" => list(map.iter().map(|(key, _)| key.clone()).collect()),
+        "values" => list(map.iter().map(|(_, value)| value.clone()).collect()),
+        "has" => Literal::Bool(map.get(&arguments[0]).is_some()),
+        // whether there was anything to delete
+        "delete" => Literal::Bool(map.remove(&arguments[0]).is_some()),
+        _ => unreachable!("arity is only known for map methods"),
+    })
+}
+
+/// check that `index` is an integer that can index into a list of length `len`. with
+/// `allow_end`, the length itself is allowed too, e.g. for inserting at the end
+fn list_index(
+    token: &Token,
+    index: &Literal,
+    len: usize,
+    allow_end: bool,
+) -> Result<usize, RuntimeError> {
+    let limit = if allow_end { len + 1 } else { len };
+    match index {
+        Literal::Integer(int) if (0..limit as i64).contains(int) => Ok(*int as usize),
+        Literal::Integer(_) | Literal::BigInt(_) => Err(RuntimeError::new(
+            "runtime-index-out-of-range",
+            token,
+            format!(
+                "Index {} is out of range for a list of length {}.",
+                index, len
+            ),
+        )),
+        Literal::Number(_) => Err(RuntimeError::new(
+            "runtime-bad-index",
+            token,
+            format!("List index must be an integer, not {}.", index),
+        )),
+        _ => Err(RuntimeError::new(
+            "runtime-bad-index",
+            token,
+            format!(
+                "List index must be an integer, not a {}.",
+                index.kind_name()
+            ),
+        )),
+    }
+}
+
+fn range_bound(operator: &Token, bound: &Literal) -> Result<i64, RuntimeError> {
+    match bound {
+        Literal::Integer(int) => Ok(*int),
+        Literal::BigInt(_) => Err(RuntimeError::new(
+            "runtime-range-bound-too-large",
+            operator,
+            format!("Range bound {} is too large.", bound),
+        )),
+        Literal::Number(_) => Err(RuntimeError::new(
+            "runtime-bad-range-bound",
+            operator,
+            format!("Range bounds must be integers, not {}.", bound),
+        )),
+        _ => Err(RuntimeError::new(
+            "runtime-bad-range-bound",
+            operator,
+            format!(
+                "Range bounds must be integers, not a {}.",
+                bound.kind_name()
+            ),
+        )),
+    }
+}
+
+fn not_indexable(bracket: &Token) -> RuntimeError {
+    RuntimeError::new(
+        "runtime-not-indexable",
+        bracket,
+        "Can only index into lists and maps.".to_string(),
+    )
+}
+
+fn nan_key(token: &Token) -> RuntimeError {
+    RuntimeError::new(
+        "runtime-nan-key",
+        token,
+        "Map keys can't be nan.".to_string(),
+    )
+}
+
+fn unary(operator: &Token, right: Literal) -> Result<Literal, RuntimeError> {
+    Ok(match (&operator.kind, right) {
+        (TokenKind::Bang, right) => Literal::Bool(!right.is_truthy()),
+        (TokenKind::Minus, Literal::Number(num)) => Literal::Number(-num),
+        (TokenKind::Minus, Literal::Integer(num)) => match num.checked_neg() {
+            Some(num) => Literal::Integer(num),
+            None => (-&BigInt::from(num)).into(),
+        },
+        (TokenKind::Minus, Literal::BigInt(num)) => (-&num).into(),
+        (_, right) => {
+            return Err(RuntimeError::new(
+                "runtime-operand-type",
+                operator,
+                format!(
+                    "Operator '{}' cannot be applied to a value of type {}.",
+                    operator.name(),
+                    right.kind_name()
+                ),
+            ))
+        }
+    })
+}
+
+fn binary(operator: &Token, left: Literal, right: Literal) -> Result<Literal, RuntimeError> {
+    Ok(match (&operator.kind, left, right) {
+        // Plus is overloaded so we handle a few cases
+        (TokenKind::Plus, Literal::Str(mut left), Literal::Str(right)) => {
+            left.push_str(&right);
+            Literal::Str(left)
+        }
+        (TokenKind::Plus, left, Literal::Str(right)) => {
+            let mut left = left.to_string();
+            left.push_str(&right);
+            Literal::Str(left)
+        }
+        (TokenKind::Plus, Literal::Str(mut left), right) => {
+            left.push_str(&right.to_string());
+            Literal::Str(left)
+        }
+
+        (TokenKind::BangEqual, left, right) => Literal::Bool(left != right),
+        (TokenKind::EqualEqual, left, right) => Literal::Bool(left == right),
+        (TokenKind::DotDot | TokenKind::DotDotEqual, left, right) => Literal::Range {
+            start: range_bound(operator, &left)?,
+            end: range_bound(operator, &right)?,
+            inclusive: operator.kind == TokenKind::DotDotEqual,
+        },
+
+        (_, Literal::Integer(left), Literal::Integer(right)) => {
+            integer_arithmetic(operator, left, right)?
+        }
+        (_, Literal::BigInt(left), Literal::BigInt(right)) => {
+            big_arithmetic(operator, &left, &right)?
+        }
+        (_, Literal::BigInt(left), Literal::Integer(right)) => {
+            big_arithmetic(operator, &left, &right.into())?
+        }
+        (_, Literal::Integer(left), Literal::BigInt(right)) => {
+            big_arithmetic(operator, &left.into(), &right)?
+        }
+        // comparisons are exact rather than promoting to float, so they agree with `==`
+        (
+            TokenKind::Greater | TokenKind::GreaterEqual | TokenKind::Less | TokenKind::LessEqual,
+            left,
+            right,
+        ) if left.as_float().is_some() && right.as_float().is_some() => {
+            let ordering = left.compare_number(&right);
+            Literal::Bool(match operator.kind {
+                TokenKind::Greater => ordering == Some(Ordering::Greater),
+                TokenKind::GreaterEqual => ordering.is_some_and(Ordering::is_ge),
+                TokenKind::Less => ordering == Some(Ordering::Less),
+                _ => ordering.is_some_and(Ordering::is_le),
+            })
+        }
+        // mixing integers and floats promotes to float
+        (_, left, right) if left.as_float().is_some() && right.as_float().is_some() => {
+            float_arithmetic(
+                operator,
+                left.as_float().unwrap(),
+                right.as_float().unwrap(),
+            )?
+        }
+
+        (_, left, right) => {
+            return Err(RuntimeError::new(
+                "runtime-operand-type",
+                operator,
+                format!(
+                    "Operator '{}' cannot be applied to values of type {} and {}.",
+                    operator.name(),
+                    left.kind_name(),
+                    right.kind_name()
+                ),
+            ))
+        }
+    })
+}
+
+/// apply a binary operator to two integers. results stay exact, except for `/` which always
+/// produces a float, and `**` with a negative exponent
+fn integer_arithmetic(operator: &Token, left: i64, right: i64) -> Result<Literal, RuntimeError> {
+    let exact = match operator.kind {
+        TokenKind::Plus => left.checked_add(right),
+        TokenKind::Minus => left.checked_sub(right),
+        TokenKind::Star => left.checked_mul(right),
+        // both round towards negative infinity, to agree with the float versions
+        TokenKind::Percent if right != 0 => {
+            let remainder = left.wrapping_rem(right);
+            if remainder != 0 && (remainder < 0) != (right < 0) {
+                Some(remainder + right)
+            } else {
+                Some(remainder)
+            }
+        }
+        TokenKind::TildeSlash if right != 0 => left.checked_div(right).map(|quotient| {
+            if left % right != 0 && (left < 0) != (right < 0) {
+                quotient - 1
+            } else {
+                quotient
+            }
+        }),
+        TokenKind::StarStar => u32::try_from(right)
+            .ok()
+            .and_then(|exponent| left.checked_pow(exponent)),
+        TokenKind::Greater => return Ok(Literal::Bool(left > right)),
+        TokenKind::GreaterEqual => return Ok(Literal::Bool(left >= right)),
+        TokenKind::Less => return Ok(Literal::Bool(left < right)),
+        TokenKind::LessEqual => return Ok(Literal::Bool(left <= right)),
+        _ => None,
+    };
+    match exact {
+        Some(result) => Ok(Literal::Integer(result)),
+        // the result overflowed, or it's a case only the general version handles
+        None => big_arithmetic(operator, &left.into(), &right.into()),
+    }
+}
+
+/// the largest power `**` will compute, in bits. a result this size takes around a second to
+/// compute and print
+const MAX_POWER_BITS: u64 = 1 << 20;
+
+/// integer arithmetic that can't overflow. results that fit in an `i64` are turned back into
+/// ordinary integers
+fn big_arithmetic(
+    operator: &Token,
+    left: &BigInt,
+    right: &BigInt,
+) -> Result<Literal, RuntimeError> {
+    Ok(match operator.kind {
+        TokenKind::Plus => (left + right).into(),
+        TokenKind::Minus => (left - right).into(),
+        TokenKind::Star => (left * right).into(),
+        TokenKind::Slash => float_arithmetic(operator, left.to_f64(), right.to_f64())?,
+        TokenKind::Percent | TokenKind::TildeSlash => {
+            let (quotient, remainder) = left.div_mod(right).ok_or_else(|| {
+                RuntimeError::new(
+                    "runtime-division-by-zero",
+                    operator,
+                    "Division by zero.".to_string(),
+                )
+            })?;
+            match operator.kind {
+                TokenKind::Percent => remainder.into(),
+                _ => quotient.into(),
+            }
+        }
+        TokenKind::StarStar if right.is_negative() => {
+            float_arithmetic(operator, left.to_f64(), right.to_f64())?
+        }
+        TokenKind::StarStar => match right.to_i64().and_then(|exp| u32::try_from(exp).ok()) {
+            // `bits * exponent` is a bound on the size of the result, checked up front so that
+            // a huge power fails right away rather than taking minutes to compute
+            Some(exponent) if left.bits() * u64::from(exponent) <= MAX_POWER_BITS => {
+                left.pow(exponent).into()
+            }
+            // only these bases survive exponents this large
+            _ => match left.to_i64() {
+                Some(0) | Some(1) => left.clone().into(),
+                Some(-1) if right.is_odd() => Literal::Integer(-1),
+                Some(-1) => Literal::Integer(1),
+                _ => {
+                    return Err(RuntimeError::new(
+                        "runtime-exponent-too-large",
+                        operator,
+                        "Exponent too large.".to_string(),
+                    ))
+                }
+            },
+        },
+        TokenKind::Greater => Literal::Bool(left > right),
+        TokenKind::GreaterEqual => Literal::Bool(left >= right),
+        TokenKind::Less => Literal::Bool(left < right),
+        TokenKind::LessEqual => Literal::Bool(left <= right),
+        _ => unreachable!("{:?} is not an arithmetic operator", operator.kind),
+    })
+}
+
+fn float_arithmetic(operator: &Token, left: f64, right: f64) -> Result<Literal, RuntimeError> {
+    Ok(match operator.kind {
+        TokenKind::Plus => Literal::Number(left + right),
+        TokenKind::Minus => Literal::Number(left - right),
+        TokenKind::Star => Literal::Number(left * right),
+        // division follows IEEE 754, so dividing by zero gives an infinity or nan
+        TokenKind::Slash => Literal::Number(left / right),
+        TokenKind::Percent | TokenKind::TildeSlash if right == 0.0 => {
+            return Err(RuntimeError::new(
+                "runtime-division-by-zero",
+                operator,
+                "Division by zero.".to_string(),
+            ))
+        }
+        // modulo is floored, so the result takes the sign of the divisor
+        TokenKind::Percent => Literal::Number(left - right * (left / right).floor()),
+        TokenKind::TildeSlash => Literal::Number((left / right).floor()),
+        TokenKind::StarStar => Literal::Number(left.powf(right)),
+        TokenKind::Greater => Literal::Bool(left > right),
+        TokenKind::GreaterEqual => Literal::Bool(left >= right),
+        TokenKind::Less => Literal::Bool(left < right),
+        TokenKind::LessEqual => Literal::Bool(left <= right),
+        _ => unreachable!("{:?} is not an arithmetic operator", operator.kind),
+    })
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{lex::lex, parse::parse, resolve::resolve};
+
+    /// run a program and hand back its global scope so tests can inspect the results
+    fn run(source: &str) -> Result<Rc<RefCell<Environment>>, RuntimeError> {
+        let statements = parse(lex("<for testing>".to_string(), source).0).0;
+        resolve(&statements).expect("test programs should resolve");
+        let mut interpreter = Interpreter::new();
+        interpreter.interpret(statements)?;
+        Ok(interpreter.globals)
+    }
+
+    fn error(source: &str) -> String {
+        run(source)
+            .err()
+            .expect("expected a runtime error")
+            .to_string()
+    }
+
+    /// evaluate a single expression
+    fn eval(expr: &str) -> Literal {
+        let globals = run(&format!("var result = {};", expr)).unwrap();
+        let result = globals.borrow().get("result").unwrap();
+        result
+    }
+
+    #[test]
+    fn test_closures() {
+        let globals = run("
+            fun makeCounter() {
+                var i = 0;
+                fun count() { i = i + 1; return i; }
+                return count;
+            }
+            var counter = makeCounter();
+            counter();
+            var result = counter();
+        ")
+        .unwrap();
+        assert_eq!(globals.borrow().get("result"), Some(Literal::Integer(2)));
+    }
+
+    #[test]
+    fn test_closure_sees_later_mutation() {
+        let globals = run("
+            var before;
+            var after;
+            {
+                var captured = \"old\";
+                fun show() { return captured; }
+                before = show();
+                captured = \"new\";
+                after = show();
+            }
+        ")
+        .unwrap();
+        assert_eq!(
+            globals.borrow().get("before"),
+            Some(Literal::Str("old".to_string()))
+        );
+        assert_eq!(
+            globals.borrow().get("after"),
+            Some(Literal::Str("new".to_string()))
+        );
+    }
+
+    #[test]
+    fn test_classes() {
+        let globals = run("
+            class Point {
+                init(x, y) { this.x = x; this.y = y; }
+                sum() { return this.x + this.y; }
+                label() { return \"method\"; }
+            }
+            var p = Point(1, 2);
+            var sum = p.sum();
+            // a method taken off an instance stays bound to it
+            var m = p.sum;
+            var bound = m();
+            // calling init again reruns it and hands back the instance
+            var same = p.init(3, 4) == p;
+            var rebound = m();
+            p.label = \"field\";
+            var label = p.label;
+        ")
+        .unwrap();
+        let get = |name| globals.borrow().get(name).unwrap();
+        assert_eq!(get("sum"), Literal::Integer(3));
+        assert_eq!(get("bound"), Literal::Integer(3));
+        assert_eq!(get("same"), Literal::Bool(true));
+        assert_eq!(get("rebound"), Literal::Integer(7));
+        // fields shadow methods of the same name
+        assert_eq!(get("label"), Literal::Str("field".to_string()));
+
+        assert_eq!(
+            error("var n = 1;\nn.x = 2;"),
+            "[line 2, column 3] Error: Only instances have fields."
+        );
+        assert_eq!(
+            error("var n = 1;\nprint n.x;"),
+            "[line 2, column 9] Error: Only instances have properties."
+        );
+        assert_eq!(
+            error("class A {}\nprint A().x;"),
+            "[line 2, column 11] Error: Undefined property 'x'."
+        );
+    }
+
+    #[test]
+    fn test_inheritance() {
+        let globals = run("
+            class A {
+                name() { return \"A\"; }
+                describe() { return \"A\"; }
+            }
+            class B < A {
+                name() { return \"B\"; }
+                describe() { return \"B \" + super.describe(); }
+                parent() { return super.name(); }
+            }
+            class C < B {
+                name() { return \"C\"; }
+                describe() { return \"C \" + super.describe(); }
+            }
+            var chain = C().describe();
+            // `super` in B's method means A, even when called on a C
+            var parent = C().parent();
+        ")
+        .unwrap();
+        assert_eq!(
+            globals.borrow().get("chain"),
+            Some(Literal::Str("C B A".to_string()))
+        );
+        assert_eq!(
+            globals.borrow().get("parent"),
+            Some(Literal::Str("A".to_string()))
+        );
+        assert_eq!(
+            error("var NotAClass = 1;\nclass A < NotAClass {}"),
+            "[line 2, column 11] Error: Superclass must be a class.",
+        );
+    }
+
+    #[test]
+    fn test_type_error() {
+        assert_eq!(
+            error("var a = 1;\nvar b = a - \"two\";"),
+            "[line 2, column 11] Error: Operator '-' cannot be applied to values of type number and string.",
+        );
+    }
+
+    #[test]
+    fn test_undefined_variable() {
+        assert_eq!(
+            error("a = 1;"),
+            "[line 1, column 1] Error: Undefined variable 'a'."
+        );
+        assert_eq!(
+            error("print a;"),
+            "[line 1, column 7] Error: Undefined variable 'a'."
+        );
+    }
+
+    #[test]
+    fn test_arity() {
+        assert_eq!(
+            error("fun f(a, b) {}\nf(1);"),
+            "[line 2, column 4] Error: Expected 2 arguments but got 1.",
+        );
+    }
+
+    #[test]
+    fn test_error_aborts() {
+        let mut interpreter = Interpreter::new();
+        let statements = parse(lex("<for testing>".to_string(), "var a = 1; -nil; a = 2;").0).0;
+        assert!(interpreter.interpret(statements).is_err());
+        assert_eq!(
+            interpreter.globals.borrow().get("a"),
+            Some(Literal::Integer(1))
+        );
+    }
+
+    #[test]
+    fn test_stack_overflow() {
+        // the test thread's default stack is too small to reach the limit in a debug build
+        std::thread::Builder::new()
+            .stack_size(STACK_SIZE)
+            .spawn(|| {
+                let mut interpreter = Interpreter::new();
+                let source = "fun f(n) { if (n == 0) return 0; return 1 + f(n - 1); }\nf(5000);";
+                let statements = parse(lex("<for testing>".to_string(), source).0).0;
+                resolve(&statements).unwrap();
+                assert_eq!(
+                    interpreter.interpret(statements).unwrap_err().to_string(),
+                    "[line 1, column 52] Error: Stack overflow."
+                );
+
+                // the depth is back to zero afterwards, so deep but finite recursion still works
+                let source = "var result = f(900);";
+                let statements = parse(lex("<for testing>".to_string(), source).0).0;
+                interpreter.interpret(statements).unwrap();
+                assert_eq!(
+                    interpreter.globals.borrow().get("result"),
+                    Some(Literal::Integer(900))
+                );
+            })
+            .unwrap()
+            .join()
+            .unwrap();
+    }
+
+    #[test]
+    fn test_division() {
+        assert_eq!(eval("7 / 2"), Literal::Number(3.5));
+        assert_eq!(eval("1 / 0"), Literal::Number(f64::INFINITY));
+        assert_eq!(eval("-1 / 0"), Literal::Number(f64::NEG_INFINITY));
+        assert!(matches!(eval("0 / 0"), Literal::Number(num) if num.is_nan()));
+    }
+
+    #[test]
+    fn test_modulo() {
+        assert_eq!(eval("7 % 3"), Literal::Integer(1));
+        assert_eq!(eval("-7 % 3"), Literal::Integer(2));
+        assert_eq!(eval("7 % -3"), Literal::Integer(-2));
+        assert_eq!(eval("5.5 % 2"), Literal::Number(1.5));
+        assert_eq!(
+            error("1 % 0;"),
+            "[line 1, column 3] Error: Division by zero."
+        );
+    }
+
+    #[test]
+    fn test_floor_division() {
+        assert_eq!(eval("7 ~/ 2"), Literal::Integer(3));
+        assert_eq!(eval("-7 ~/ 2"), Literal::Integer(-4));
+        assert_eq!(eval("7.5 ~/ 0.5"), Literal::Number(15.0));
+        assert_eq!(
+            error("1 ~/ 0;"),
+            "[line 1, column 3] Error: Division by zero."
+        );
+    }
+
+    #[test]
+    fn test_power() {
+        assert_eq!(eval("2 ** 10"), Literal::Integer(1024));
+        assert_eq!(eval("2 ** 3 ** 2"), Literal::Integer(512));
+        assert_eq!(eval("-2 ** 2"), Literal::Integer(-4));
+        assert_eq!(eval("2 ** -1"), Literal::Number(0.5));
+        assert_eq!(eval("2 * 3 ** 2"), Literal::Integer(18));
+    }
+
+    #[test]
+    fn test_integers() {
+        // beyond 2^53, where floats can no longer represent every integer
+        assert!(matches!(
+            eval("9007199254740993 + 2"),
+            Literal::Integer(9_007_199_254_740_995)
+        ));
+        assert!(matches!(eval("3 * 4 - 5"), Literal::Integer(7)));
+        assert!(matches!(eval("1 + 0.5"), Literal::Number(num) if num == 1.5));
+        assert!(matches!(eval("6 / 3"), Literal::Number(num) if num == 2.0));
+        assert_eq!(eval("1 == 1.0"), Literal::Bool(true));
+        assert_eq!(
+            eval("9007199254740993 > 9007199254740992"),
+            Literal::Bool(true)
+        );
+    }
+
+    #[test]
+    fn test_bignums() {
+        let globals = run("
+            fun factorial(n) { if (n <= 1) return 1; return n * factorial(n - 1); }
+            var result = factorial(50);
+        ")
+        .unwrap();
+        assert_eq!(
+            globals.borrow().get("result").unwrap().to_string(),
+            "30414093201713378043612608166064768844377641568960512000000000000",
+        );
+        assert_eq!(
+            eval("9223372036854775807 + 1").to_string(),
+            "9223372036854775808"
+        );
+        assert_eq!(eval("2 ** 64").to_string(), "18446744073709551616");
+        assert_eq!(
+            eval("-(-9223372036854775807 - 1)").to_string(),
+            "9223372036854775808"
+        );
+        // results that fit are ordinary integers again
+        assert!(matches!(eval("2 ** 64 - 2 ** 64 + 1"), Literal::Integer(1)));
+        assert!(matches!(
+            eval("(2 ** 64) ~/ (2 ** 62)"),
+            Literal::Integer(4)
+        ));
+        assert!(matches!(eval("-(2 ** 64) % 3"), Literal::Integer(2)));
+        assert_eq!(eval("2 ** 64 > 2 ** 63"), Literal::Bool(true));
+        assert_eq!(
+            eval("2 ** 64 == 18446744073709551616.0"),
+            Literal::Bool(true)
+        );
+        assert_eq!(
+            eval("(2 ** 64) / 2"),
+            Literal::Number(9223372036854775808.0)
+        );
+        assert_eq!(
+            error("print (2 ** 64) % 0;"),
+            "[line 1, column 17] Error: Division by zero."
+        );
+        assert_eq!(
+            error("print 2 ** (2 ** 40);"),
+            "[line 1, column 9] Error: Exponent too large."
+        );
+        assert_eq!(
+            error("print 3 ** 4000000000;"),
+            "[line 1, column 9] Error: Exponent too large."
+        );
+        assert_eq!(
+            error("print (2 ** 64) ** 20000;"),
+            "[line 1, column 17] Error: Exponent too large."
+        );
+        assert_eq!(eval("(-1) ** 4000000001"), Literal::Integer(-1));
+        assert_eq!(eval("0 ** 4000000000"), Literal::Integer(0));
+        assert_eq!(eval("(3 ** 20000) % 10"), Literal::Integer(1));
+    }
+
+    #[test]
+    fn test_mixed_comparisons() {
+        let cases = [
+            // 2^53 + 1 has no float, so promoting it would make it equal to 2^53
+            ("9007199254740993 > 9007199254740992.0", true),
+            ("9007199254740993 >= 9007199254740992.0", true),
+            ("9007199254740993 < 9007199254740992.0", false),
+            ("9007199254740992.0 < 9007199254740993", true),
+            ("9007199254740992 <= 9007199254740992.0", true),
+            ("2 ** 70 + 1 > 2.0 ** 70", true),
+            ("2 < 2.5", true),
+            ("-3 < -2.5", true),
+            ("-2 < -2.5", false),
+            ("2 ** 64 < inf", true),
+            ("-inf < -(2 ** 64)", true),
+            ("1 < nan", false),
+            ("1 >= nan", false),
+            ("nan <= 2 ** 64", false),
+        ];
+        for (expr, expected) in cases {
+            assert_eq!(eval(expr), Literal::Bool(expected), "{}", expr);
+        }
+    }
+
+    #[test]
+    fn test_interpolation() {
+        assert_eq!(
+            eval(r#""x=${1 + 1}, y=${"${nil}!"} ${1.5}""#),
+            Literal::Str("x=2, y=nil! 1.5".to_string())
+        );
+        let globals = run(r#"
+            class Point { init(x) { this.x = x; } }
+            var p = Point(3);
+            var result = "${p} at ${p.x}";
+        "#)
+        .unwrap();
+        assert_eq!(
+            globals.borrow().get("result"),
+            Some(Literal::Str("Point instance at 3".to_string()))
+        );
+    }
+
+    #[test]
+    fn test_break_and_continue() {
+        let globals = run("
+            var evens = 0;
+            for (var i = 0; i < 10; i = i + 1) {
+                if (i == 7) break;
+                if (i % 2 == 1) continue;
+                evens = evens + 1;
+            }
+            var n = 0;
+            while (true) {
+                n = n + 1;
+                if (n < 5) continue;
+                break;
+            }
+        ")
+        .unwrap();
+        // 0, 2, 4 and 6
+        assert_eq!(globals.borrow().get("evens"), Some(Literal::Integer(4)));
+        assert_eq!(globals.borrow().get("n"), Some(Literal::Integer(5)));
+    }
+
+    #[test]
+    fn test_labeled_loops() {
+        let globals = run("
+            var pairs = \"\";
+            outer: for (var i = 0; i < 4; i = i + 1) {
+                var j = 0;
+                inner: while (true) {
+                    j = j + 1;
+                    if (j > i) continue outer;
+                    if (i == 3) break outer;
+                    pairs = pairs + \"${i}${j} \";
+                }
+            }
+        ")
+        .unwrap();
+        assert_eq!(
+            globals.borrow().get("pairs"),
+            Some(Literal::Str("11 21 22 ".to_string()))
+        );
+    }
+
+    #[test]
+    fn test_lists() {
+        let globals = run("
+            var xs = [1, 2, 3,];
+            var ys = xs;
+            ys[0] = 10;
+            xs.push(4);
+            xs.insert(1, 5);
+            var popped = xs.pop();
+            var removed = xs.remove(2);
+            var result = \"${xs} ${ys.len()} ${popped} ${removed} ${xs[1:]} ${xs[:1]} ${xs[:]}\";
+        ")
+        .unwrap();
+        assert_eq!(
+            globals.borrow().get("result"),
+            Some(Literal::Str(
+                "[10, 5, 3] 3 4 2 [5, 3] [10] [10, 5, 3]".to_string()
+            ))
+        );
+        assert_eq!(eval("[[], \"a\", nil]").to_string(), r#"[[], "a", nil]"#);
+        assert_eq!(eval("[1] == [1]"), Literal::Bool(false));
+    }
+
+    #[test]
+    fn test_list_errors() {
+        assert_eq!(
+            error("var xs = [1, 2];\nprint xs[2];"),
+            "[line 2, column 9] Error: Index 2 is out of range for a list of length 2.",
+        );
+        assert_eq!(
+            error("var xs = [1, 2];\nxs[-1] = 0;"),
+            "[line 2, column 3] Error: Index -1 is out of range for a list of length 2.",
+        );
+        assert_eq!(
+            error("print [1][0.5];"),
+            "[line 1, column 10] Error: List index must be an integer, not 0.5.",
+        );
+        assert_eq!(
+            error("print [1][\"0\"];"),
+            "[line 1, column 10] Error: List index must be an integer, not a string.",
+        );
+        assert_eq!(
+            error("print [1, 2, 3][2:1];"),
+            "[line 1, column 16] Error: Slice starts at 2 but ends before it at 1.",
+        );
+        assert_eq!(
+            error("[].pop();"),
+            "[line 1, column 8] Error: Can't pop from an empty list.",
+        );
+        assert_eq!(
+            error("print 1[0];"),
+            "[line 1, column 8] Error: Can only index into lists and maps."
+        );
+    }
+
+    #[test]
+    fn test_maps() {
+        let globals = run("
+            var m = {\"b\": 1, \"a\": [2], 3: nil,};
+            var alias = m;
+            alias[\"c\"] = 4;
+            m[\"b\"] = 5;
+            m[1.0] = \"one\";
+            var deleted = \"${m.delete(3)} ${m.delete(3)}\";
+            var result = \"${m} ${m[\"a\"]} ${m[1]} ${m.has(\"c\")} ${m.has(4)} ${m.len()} ${deleted}\";
+            var parts = \"${m.keys()} ${m.values()}\";
+        ")
+        .unwrap();
+        assert_eq!(
+            globals.borrow().get("result"),
+            Some(Literal::Str(
+                r#"{"b": 5, "a": [2], "c": 4, 1: "one"} [2] one true false 4 true false"#
+                    .to_string()
+            ))
+        );
+        assert_eq!(
+            globals.borrow().get("parts"),
+            Some(Literal::Str(
+                r#"["b", "a", "c", 1] [5, [2], 4, "one"]"#.to_string()
+            ))
+        );
+        assert_eq!(eval("{}").to_string(), "{}");
+        assert_eq!(eval("{0.5: 1, -0.0: 2}[0]"), Literal::Integer(2));
+        assert_eq!(eval("{} == {}"), Literal::Bool(false));
+
+        // at the start of a statement, a brace followed by a key and a colon is a map
+        let globals = run("
+            var result = 0;
+            {\"a\": 1}.len();
+            { outer: while (true) { result = 1; break outer; } }
+        ")
+        .unwrap();
+        assert_eq!(globals.borrow().get("result"), Some(Literal::Integer(1)));
+    }
+
+    #[test]
+    fn test_map_errors() {
+        assert_eq!(
+            error("var m = {\"a\": 1};\nprint m[\"b\"];"),
+            "[line 2, column 8] Error: Undefined key 'b'.",
+        );
+        assert_eq!(
+            error("var m = {};\nm[0/0] = 1;"),
+            "[line 2, column 2] Error: Map keys can't be nan.",
+        );
+        assert_eq!(
+            error("print {0/0: 1};"),
+            "[line 1, column 7] Error: Map keys can't be nan.",
+        );
+        assert_eq!(
+            error("var keys = {}.keys;"),
+            "[line 1, column 15] Error: Map methods must be called right away, e.g. 'm.keys()'.",
+        );
+        assert_eq!(
+            error("print {}[0:1];"),
+            "[line 1, column 9] Error: Can only slice lists.",
+        );
+    }
+
+    #[test]
+    fn test_for_in() {
+        let globals = run("
+            class Countdown {
+                init(from) { this.from = from; }
+                iter() { return CountdownIterator(this.from); }
+            }
+            class CountdownIterator {
+                init(n) { this.n = n; }
+                next() {
+                    if (this.n == 0) return nil;
+                    this.n = this.n - 1;
+                    return this.n + 1;
+                }
+            }
+            class Wrapper {
+                iter() { return [\"w\"]; }
+            }
+
+            var result = \"\";
+            for (var x in [1, 2]) result = result + x;
+            for (key in {\"a\": 1, \"b\": 2}) result = result + key;
+            for (var c in \"hé\") result = result + c;
+            for (var i in 0..3) result = result + i;
+            for (var i in 3..=4) result = result + i;
+            for (var i in 1..1) result = result + \"!\";
+            for (var n in Countdown(3)) result = result + n;
+            for (var w in Wrapper()) result = result + w;
+            for (var n in CountdownIterator(2)) result = result + n;
+
+            var closures = [];
+            for (var i in 0..3) {
+                if (i == 1) continue;
+                fun get() {
+                    return i;
+                }
+                closures.push(get);
+            }
+            var captured = \"${closures[0]()} ${closures[1]()}\";
+
+            var pairs = \"\";
+            outer: for (var i in 0..10) {
+                for (var j in [0, 1, 2]) {
+                    if (j > i) continue outer;
+                    if (i == 3) break outer;
+                    pairs = pairs + \"${i}${j} \";
+                }
+            }
+
+            var xs = [1];
+            for (var x in xs) if (x < 3) xs.push(x + 1);
+        ")
+        .unwrap();
+        assert_eq!(
+            globals.borrow().get("result"),
+            Some(Literal::Str("12abhé01234321w21".to_string()))
+        );
+        assert_eq!(
+            globals.borrow().get("captured"),
+            Some(Literal::Str("0 2".to_string()))
+        );
+        assert_eq!(
+            globals.borrow().get("pairs"),
+            Some(Literal::Str("00 10 11 20 21 22 ".to_string()))
+        );
+        assert_eq!(globals.borrow().get("xs").unwrap().to_string(), "[1, 2, 3]");
+        assert_eq!(eval("0..=2").to_string(), "0..=2");
+        assert_eq!(eval("1..2 + 3 == 1..5"), Literal::Bool(true));
+    }
+
+    #[test]
+    fn test_for_in_next_field() {
+        // only methods count for the iterator protocol, so a `next` field is just a field
+        let globals = run("
+            class Node {
+                init(value, next) {
+                    this.value = value;
+                    this.next = next;
+                }
+                iter() { return NodeIterator(this); }
+            }
+            class NodeIterator {
+                init(node) { this.node = node; }
+                next() {
+                    if (this.node == nil) return nil;
+                    var value = this.node.value;
+                    this.node = this.node.next;
+                    return value;
+                }
+            }
+            class Single {
+                init(value) {
+                    this.value = value;
+                    this.next = nil;
+                }
+                iter() { return [this.value]; }
+            }
+
+            var result = \"\";
+            for (var x in Node(1, Node(2, Node(3, nil)))) result = result + x;
+            for (var x in Single(4)) result = result + x;
+        ")
+        .unwrap();
+        assert_eq!(
+            globals.borrow().get("result"),
+            Some(Literal::Str("1234".to_string()))
+        );
+        assert_eq!(
+            error("class A { init() { this.next = 1; } }\nfor (var x in A()) print x;"),
+            "[line 2, column 12] Error: Can't loop over an instance of 'A' without an 'iter' or 'next' method.",
+        );
+    }
+
+    #[test]
+    fn test_for_in_errors() {
+        assert_eq!(
+            error("for (var x in 12) print x;"),
+            "[line 1, column 12] Error: Can't loop over a number.",
+        );
+        assert_eq!(
+            error("class A {}\nfor (var x in A()) print x;"),
+            "[line 2, column 12] Error: Can't loop over an instance of 'A' without an 'iter' or 'next' method.",
+        );
+        assert_eq!(
+            error("class A { iter() { return this; } }\nfor (var x in A()) print x;"),
+            "[line 2, column 12] Error: The instance of 'A' returned by 'iter' has no 'next' method.",
+        );
+        assert_eq!(
+            error("var r = 0..0.5;"),
+            "[line 1, column 10] Error: Range bounds must be integers, not 0.5.",
+        );
+    }
+
+    #[test]
+    fn test_natives() {
+        let globals = run("
+            var start = clock();
+            var elapsed = clock() - start;
+            var name = \"${clock}\";
+            var same = clock == clock;
+        ")
+        .unwrap();
+        let elapsed = globals.borrow().get("elapsed").unwrap();
+        assert!(matches!(elapsed, Literal::Number(elapsed) if elapsed >= 0.0));
+        assert_eq!(
+            globals.borrow().get("name"),
+            Some(Literal::Str("<native fn clock>".to_string()))
+        );
+        assert_eq!(globals.borrow().get("same"), Some(Literal::Bool(true)));
+        assert_eq!(
+            error("clock(1);"),
+            "[line 1, column 8] Error: Expected 0 arguments but got 1."
+        );
+        // natives are ordinary globals, so they can be shadowed
+        assert_eq!(
+            run("var clock = 1;").unwrap().borrow().get("clock"),
+            Some(Literal::Integer(1))
+        );
+    }
+
+    #[test]
+    fn test_math() {
+        let cases = [
+            ("sqrt(16)", "4"),
+            ("sqrt(2)", "1.4142135623730951"),
+            ("sqrt(-1)", "nan"),
+            ("pow(2, 10)", "1024"),
+            ("pow(2, -1)", "0.5"),
+            ("pow(9, 0.5)", "3"),
+            ("abs(-3)", "3"),
+            ("abs(-2.5)", "2.5"),
+            ("abs(-9223372036854775807 - 1)", "9223372036854775808"),
+            ("floor(2.7)", "2"),
+            ("floor(-2.5)", "-3"),
+            ("ceil(2.1)", "3"),
+            ("ceil(-2.1)", "-2"),
+            ("round(2.5)", "3"),
+            ("round(-2.5)", "-3"),
+            ("round(2.4)", "2"),
+            ("floor(1e20)", "100000000000000000000"),
+            ("floor(inf)", "inf"),
+            ("min(3, 1, 2)", "1"),
+            ("max(1, 2.5, -4)", "2.5"),
+            ("max(7)", "7"),
+            ("min(1, nan)", "nan"),
+            (
+                "max(9007199254740992.0, 9007199254740993)",
+                "9007199254740993",
+            ),
+            (
+                "max(9223372036854775807, 9223372036854775808)",
+                "9223372036854775808",
+            ),
+            ("sin(0)", "0"),
+            ("sin(pi / 2)", "1"),
+            ("cos(0)", "1"),
+            ("cos(pi)", "-1"),
+            ("tan(0)", "0"),
+            ("tan(pi / 4)", "0.9999999999999999"),
+            ("atan2(1, 1) == pi / 4", "true"),
+            ("atan2(0, -1) == pi", "true"),
+            ("log(1)", "0"),
+            ("log(e)", "1"),
+            ("exp(0)", "1"),
+            ("exp(1) == e", "true"),
+            ("pi", "3.141592653589793"),
+            ("e", "2.718281828459045"),
+            ("inf", "inf"),
+            ("-inf", "-inf"),
+            ("nan", "nan"),
+            ("is_nan(nan)", "true"),
+            ("is_nan(0 / 0)", "true"),
+            ("is_nan(1)", "false"),
+            ("is_nan(inf)", "false"),
+        ];
+        for (expr, expected) in cases {
+            assert_eq!(eval(expr).to_string(), expected, "{}", expr);
+        }
+
+        // rounding gives integers that can index into lists, while e.g. sqrt always gives floats
+        assert!(matches!(eval("floor(2.7)"), Literal::Integer(2)));
+        assert!(matches!(eval("abs(-3)"), Literal::Integer(3)));
+        assert!(matches!(eval("sqrt(16)"), Literal::Number(_)));
+        assert!(matches!(eval("min(2, 1.0)"), Literal::Number(_)));
+    }
+
+    #[test]
+    fn test_math_errors() {
+        assert_eq!(
+            error("print sqrt(\"4\");"),
+            "[line 1, column 15] Error: sqrt() expects a number as argument 1, not a string."
+        );
+        assert_eq!(
+            error("print atan2(1, nil);"),
+            "[line 1, column 19] Error: atan2() expects a number as argument 2, not a nil."
+        );
+        assert_eq!(
+            error("print max(1, 2, [3]);"),
+            "[line 1, column 20] Error: max() expects a number as argument 3, not a list."
+        );
+        assert_eq!(
+            error("print min();"),
+            "[line 1, column 11] Error: min() expects at least 1 argument."
+        );
+        assert_eq!(
+            error("print pow(2);"),
+            "[line 1, column 12] Error: Expected 2 arguments but got 1."
+        );
     }
 }
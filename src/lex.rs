@@ -1,15 +1,50 @@
+use crate::bigint::BigInt;
 use crate::token::{Range, Source, Token, TokenKind};
+use crate::unicode;
+use std::fmt;
 use std::rc::Rc;
 
-pub fn lex(name: String, source: &str) -> Vec<Token> {
+/// split source text into tokens. lexing carries on past errors so that they can all be
+/// reported at once; the tokens are only meaningful if there were no errors
+pub fn lex(name: String, source: &str) -> (Vec<Token>, Vec<LexError>) {
     Lexer::new(name, source.chars().collect()).lex()
 }
 
+#[derive(Debug)]
+pub struct LexError {
+    /// stable identifier for the kind of error, see `Diagnostic::code`
+    pub code: &'static str,
+    pub line: usize,
+    pub column: usize,
+    pub span: Range,
+    pub source: Rc<Source>,
+    pub message: String,
+}
+
+impl fmt::Display for LexError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "[line {}, column {}] Error: {}",
+            self.line, self.column, self.message
+        )
+    }
+}
+
 struct Lexer {
     tokens: Vec<Token>,
+    errors: Vec<LexError>,
     start: usize,
     current: usize,
     line: usize,
+    /// index of the first char on the current line, for working out columns
+    line_start: usize,
+    /// line and column that the lexeme currently being scanned starts at
+    start_line: usize,
+    start_column: usize,
+    /// for each `${` we're inside, how many unclosed `{` it contains, so that we know which `}`
+    /// ends the interpolation and goes back to the string
+    interpolations: Vec<usize>,
     source: Rc<Source>,
 }
 
@@ -19,20 +54,32 @@ impl Lexer {
         Self {
             source: Rc::new(source),
             tokens: Vec::new(),
+            errors: Vec::new(),
             start: 0,
             current: 0,
             line: 1,
+            line_start: 0,
+            start_line: 1,
+            start_column: 1,
+            interpolations: Vec::new(),
         }
     }
 
-    fn lex(mut self) -> Vec<Token> {
+    fn lex(mut self) -> (Vec<Token>, Vec<LexError>) {
         while !self.is_at_end() {
-            self.start = self.current;
+            self.mark_start();
             self.scan_token()
         }
+        self.mark_start();
+        if !self.interpolations.is_empty() {
+            self.error(
+                "lex-unterminated-interpolation",
+                "Unterminated string interpolation.".to_string(),
+            );
+        }
         self.add_basic_token(TokenKind::Eof);
 
-        self.tokens
+        (self.tokens, self.errors)
     }
 
     fn scan_token(&mut self) {
@@ -40,14 +87,59 @@ impl Lexer {
         match c {
             '(' => self.add_basic_token(TokenKind::LeftParen),
             ')' => self.add_basic_token(TokenKind::RightParen),
-            '{' => self.add_basic_token(TokenKind::LeftBrace),
-            '}' => self.add_basic_token(TokenKind::RightBrace),
+            '{' => {
+                if let Some(braces) = self.interpolations.last_mut() {
+                    *braces += 1;
+                }
+                self.add_basic_token(TokenKind::LeftBrace)
+            }
+            '}' => match self.interpolations.last_mut() {
+                Some(0) => {
+                    // end of an interpolated expression, so carry on with the string
+                    self.interpolations.pop();
+                    self.string();
+                }
+                Some(braces) => {
+                    *braces -= 1;
+                    self.add_basic_token(TokenKind::RightBrace)
+                }
+                None => self.add_basic_token(TokenKind::RightBrace),
+            },
+            '[' => self.add_basic_token(TokenKind::LeftBracket),
+            ']' => self.add_basic_token(TokenKind::RightBracket),
             ',' => self.add_basic_token(TokenKind::Comma),
-            '.' => self.add_basic_token(TokenKind::Dot),
+            '.' => {
+                if !self.eat('.') {
+                    self.add_basic_token(TokenKind::Dot);
+                } else if self.eat('=') {
+                    self.add_basic_token(TokenKind::DotDotEqual);
+                } else {
+                    self.add_basic_token(TokenKind::DotDot);
+                }
+            }
             '-' => self.add_basic_token(TokenKind::Minus),
             '+' => self.add_basic_token(TokenKind::Plus),
             ';' => self.add_basic_token(TokenKind::Semicolon),
-            '*' => self.add_basic_token(TokenKind::Star),
+            ':' => self.add_basic_token(TokenKind::Colon),
+            '*' => {
+                if self.eat('*') {
+                    self.add_basic_token(TokenKind::StarStar);
+                } else {
+                    self.add_basic_token(TokenKind::Star);
+                }
+            }
+            '%' => self.add_basic_token(TokenKind::Percent),
+            // floor division. spelled `~/` since `//` starts a comment
+            '~' => {
+                if self.eat('/') {
+                    self.add_basic_token(TokenKind::TildeSlash);
+                } else {
+                    self.error(
+                        "lex-unexpected-character",
+                        "Unexpected character '~'.".to_string(),
+                    );
+                }
+            }
             '!' => {
                 if self.eat('=') {
                     self.add_basic_token(TokenKind::BangEqual);
@@ -78,59 +170,286 @@ impl Lexer {
             }
             '/' => {
                 if self.eat('/') {
+                    // exactly three slashes make a doc comment, more is just a comment
+                    let is_doc = self.next_is('/') && self.peek_nth(1) != Some(&'/');
                     self.eat_while(|&c| c != '\n');
+                    if is_doc {
+                        let text = self.get_lexeme(&Range(self.start + 3, self.current));
+                        let text = text.strip_prefix(' ').unwrap_or(&text).to_string();
+                        self.add_basic_token(TokenKind::DocComment(text));
+                    }
+                } else if self.eat('*') {
+                    self.block_comment();
                 } else {
                     self.add_basic_token(TokenKind::Slash);
                 }
             }
             ' ' | '\t' | '\r' => {} // skip whitespace
-            '\n' => {
-                self.line += 1;
-            }
+            '\n' => self.newline(),
             '"' => self.string(),
             _ => {
-                if c.is_numeric() {
+                if c.is_ascii_digit() {
                     self.number();
-                } else if c.is_alphabetic() {
+                } else if *c == '_' || unicode::is_xid_start(*c) {
                     self.identifier();
                 } else {
-                    panic!("unexpected char {}", c)
+                    let message = format!("Unexpected character '{}'.", c);
+                    self.error("lex-unexpected-character", message);
+                }
+            }
+        }
+    }
+
+    /// skip a `/* */` comment, which may contain nested block comments
+    fn block_comment(&mut self) {
+        let mut depth = 1;
+        while depth > 0 {
+            match (self.peek(), self.peek_nth(1)) {
+                (None, _) => {
+                    self.error(
+                        "lex-unterminated-comment",
+                        "Unterminated block comment.".to_string(),
+                    );
+                    return;
+                }
+                (Some('*'), Some('/')) => {
+                    self.current += 2;
+                    depth -= 1;
+                }
+                (Some('/'), Some('*')) => {
+                    self.current += 2;
+                    depth += 1;
+                }
+                (Some('\n'), _) => {
+                    self.advance();
+                    self.newline();
+                }
+                _ => {
+                    self.advance();
                 }
             }
         }
     }
 
+    /// scan the rest of a string, after its opening quote or the `}` closing an interpolation
     fn string(&mut self) {
-        self.eat_while(|&ch| ch != '"');
-        if self.is_at_end() {
-            // TODO: error out here
-            return;
+        let mut text = String::new();
+        let mut valid = true;
+        loop {
+            match self.peek() {
+                None => {
+                    self.error(
+                        "lex-unterminated-string",
+                        "Unterminated string.".to_string(),
+                    );
+                    return;
+                }
+                Some('"') => break,
+                Some('$') if self.peek_nth(1) == Some(&'{') => {
+                    // the text before an interpolated expression, e.g. `"a ${`
+                    self.advance();
+                    self.advance();
+                    self.interpolations.push(0);
+                    if valid {
+                        self.add_basic_token(TokenKind::Interpolation(text));
+                    }
+                    return;
+                }
+                Some('\\') => match self.escape() {
+                    Some(c) => text.push(c),
+                    None => valid = false,
+                },
+                Some(_) => {
+                    let c = *self.advance();
+                    if c == '\n' {
+                        self.newline();
+                    }
+                    text.push(c);
+                }
+            }
         }
         self.advance();
-        let text = self.get_lexeme(&Range(self.start + 1, self.current - 1));
-        self.add_basic_token(TokenKind::Str(text))
+
+        // errors in escapes have already been reported
+        if valid {
+            self.add_basic_token(TokenKind::Str(text))
+        }
+    }
+
+    /// consume an escape sequence inside a string, reporting an error for just the escape if
+    /// it's invalid
+    fn escape(&mut self) -> Option<char> {
+        let start = self.current;
+        self.advance();
+        let c = match self.peek() {
+            // leave it for `string` to report
+            None => return None,
+            Some(_) => *self.advance(),
+        };
+        if c == '\n' {
+            self.newline();
+        }
+        let result = match c {
+            'n' => Ok('\n'),
+            't' => Ok('\t'),
+            'r' => Ok('\r'),
+            '\\' => Ok('\\'),
+            '"' => Ok('"'),
+            '$' => Ok('$'),
+            '0' => Ok('\0'),
+            // exactly two hex digits for an ASCII character, e.g. \x41
+            'x' => {
+                let digits: String = (0..2)
+                    .map_while(|_| match self.peek() {
+                        Some(c) if c.is_ascii_hexdigit() => Some(*self.advance()),
+                        _ => None,
+                    })
+                    .collect();
+                match u8::from_str_radix(&digits, 16) {
+                    Ok(code) if digits.len() == 2 && code.is_ascii() => Ok(code as char),
+                    Ok(_) if digits.len() == 2 => {
+                        Err("Character code in '\\x' escape must be at most 7F.".to_string())
+                    }
+                    _ => Err("Expected two hex digits after '\\x'.".to_string()),
+                }
+            }
+            // any unicode scalar value, e.g. \u{1F600}
+            'u' => {
+                if self.eat('{') {
+                    let digits_start = self.current;
+                    self.eat_while(|c| c.is_ascii_hexdigit());
+                    let digits = self.get_lexeme(&Range(digits_start, self.current));
+                    if !self.eat('}') || digits.is_empty() || digits.len() > 6 {
+                        Err("Expected 1 to 6 hex digits between braces after '\\u'.".to_string())
+                    } else {
+                        u32::from_str_radix(&digits, 16)
+                            .ok()
+                            .and_then(char::from_u32)
+                            .ok_or_else(|| {
+                                format!("'{}' is not a valid unicode character.", digits)
+                            })
+                    }
+                } else {
+                    Err("Expected '{' after '\\u'.".to_string())
+                }
+            }
+            c => Err(format!("Unknown escape sequence '\\{}'.", c.escape_debug())),
+        };
+
+        match result {
+            Ok(c) => Some(c),
+            Err(message) => {
+                self.error_at("lex-bad-escape", Range(start, self.current), message);
+                None
+            }
+        }
     }
 
     fn number(&mut self) {
-        self.eat_while(|&c| c.is_numeric());
-        let literal = if self.next_is('.') && self.peek_nth(1).map_or(false, |ch| ch.is_numeric()) {
+        let radix = match (self.get_current_lexeme().as_str(), self.peek()) {
+            ("0", Some('x' | 'X')) => Some((16, "hexadecimal")),
+            ("0", Some('o' | 'O')) => Some((8, "octal")),
+            ("0", Some('b' | 'B')) => Some((2, "binary")),
+            _ => None,
+        };
+        if let Some((radix, name)) = radix {
+            self.advance();
+            // take any letters too, so that e.g. `0b12` is reported instead of lexed as two tokens
+            self.eat_while(|&c| c.is_ascii_alphanumeric() || c == '_');
+            self.radix_number(radix, name);
+            return;
+        }
+
+        let mut is_float = false;
+        self.eat_while(|&c| c.is_ascii_digit() || c == '_');
+        if self.next_is('.') && self.peek_nth(1).is_some_and(|ch| ch.is_ascii_digit()) {
             // floating point, e.g. 3.14
+            is_float = true;
             self.advance();
-            self.eat_while(|ch| ch.is_numeric());
-            self.get_current_lexeme()
-                .parse()
-                .expect("TODO: real error handling")
+            self.eat_while(|&c| c.is_ascii_digit() || c == '_');
+        }
+        if self.next_is('e') || self.next_is('E') {
+            // scientific notation, e.g. 1.5e-3
+            is_float = true;
+            self.advance();
+            if !self.eat('+') {
+                self.eat('-');
+            }
+            if !self.peek().is_some_and(|ch| ch.is_ascii_digit()) {
+                self.error(
+                    "lex-missing-exponent-digits",
+                    "Missing digits in exponent.".to_string(),
+                );
+                return;
+            }
+            self.eat_while(|&c| c.is_ascii_digit() || c == '_');
+        }
+
+        let lexeme = self.get_current_lexeme();
+        if !separators_between_digits(&lexeme, 10) {
+            self.error(
+                "lex-misplaced-digit-separator",
+                "Digit separator '_' must be between digits.".to_string(),
+            );
+            return;
+        }
+        let digits = lexeme.replace('_', "");
+        let kind = if is_float {
+            digits.parse().map(TokenKind::Number).ok()
         } else {
-            // natural number, e.g. 69
-            self.get_current_lexeme()
-                .parse::<u64>()
-                .expect("TODO: real error handling") as f64 // cast int to floating point num
+            // natural number, e.g. 69, falling back to a bignum when it's too large
+            digits
+                .parse()
+                .map(TokenKind::Integer)
+                .ok()
+                .or_else(|| BigInt::parse(&digits).map(TokenKind::BigInt))
+        };
+        match kind {
+            Some(kind) => self.add_basic_token(kind),
+            None => self.error(
+                "lex-invalid-number",
+                format!("Invalid number literal '{}'.", lexeme),
+            ),
+        }
+    }
+
+    /// finish an integer literal with a prefix like `0x`, which has already been consumed
+    /// along with everything that looks like a digit
+    fn radix_number(&mut self, radix: u32, name: &str) {
+        let lexeme = self.get_current_lexeme();
+        let digits = &lexeme[2..];
+        if digits.is_empty() {
+            self.error(
+                "lex-missing-digits",
+                format!("Missing digits after '{}'.", lexeme),
+            );
+            return;
+        }
+        if let Some(bad) = digits.chars().find(|&c| c != '_' && !c.is_digit(radix)) {
+            self.error(
+                "lex-invalid-digit",
+                format!("Invalid digit '{}' in {} literal.", bad, name),
+            );
+            return;
+        }
+        if !separators_between_digits(&lexeme, radix) {
+            self.error(
+                "lex-misplaced-digit-separator",
+                "Digit separator '_' must be between digits.".to_string(),
+            );
+            return;
+        }
+
+        let digits = digits.replace('_', "");
+        let kind = match i64::from_str_radix(&digits, radix) {
+            Ok(num) => TokenKind::Integer(num),
+            Err(_) => TokenKind::BigInt(BigInt::parse_radix(&digits, radix)),
         };
-        self.add_basic_token(TokenKind::Number(literal));
+        self.add_basic_token(kind);
     }
 
     fn identifier(&mut self) {
-        self.eat_while(|c| c.is_alphanumeric());
+        self.eat_while(|&c| unicode::is_xid_continue(c));
         let text = self.get_current_lexeme();
         let kind = token_kind_for_text(&text);
         self.add_basic_token(kind);
@@ -154,13 +473,13 @@ impl Lexer {
     }
 
     fn eat_while(&mut self, predicate: fn(&char) -> bool) {
-        while self.peek().map_or(false, predicate) {
+        while self.peek().is_some_and(predicate) {
             self.advance();
         }
     }
 
     fn next_is(&self, c: char) -> bool {
-        self.peek().map_or(false, |&ch| ch == c)
+        self.peek() == Some(&c)
     }
 
     fn advance(&mut self) -> &char {
@@ -180,6 +499,36 @@ impl Lexer {
         self.current >= self.source.len()
     }
 
+    /// begin a new lexeme at the current position
+    fn mark_start(&mut self) {
+        self.start = self.current;
+        self.start_line = self.line;
+        self.start_column = self.current - self.line_start + 1;
+    }
+
+    /// call after consuming a `\n`
+    fn newline(&mut self) {
+        self.line += 1;
+        self.line_start = self.current;
+    }
+
+    /// record an error for the lexeme currently being scanned
+    fn error(&mut self, code: &'static str, message: String) {
+        self.error_at(code, Range(self.start, self.current), message);
+    }
+
+    fn error_at(&mut self, code: &'static str, span: Range, message: String) {
+        let (line, column) = self.source.position(span.0);
+        self.errors.push(LexError {
+            code,
+            line,
+            column,
+            span,
+            source: Rc::clone(&self.source),
+            message,
+        });
+    }
+
     fn add_basic_token(&mut self, kind: TokenKind) {
         self.add_token(self.token(kind));
     }
@@ -193,23 +542,39 @@ impl Lexer {
             source: Rc::clone(&self.source),
             kind,
             span: Range(self.start, self.current),
-            line: self.line,
+            line: self.start_line,
+            column: self.start_column,
         }
     }
 }
 
+/// whether every `_` in a number literal has a digit on either side of it, so `1_000` is fine
+/// but `1_`, `1__0` and `0x_1` are not
+fn separators_between_digits(lexeme: &str, radix: u32) -> bool {
+    let chars: Vec<char> = lexeme.chars().collect();
+    chars.iter().enumerate().all(|(i, &c)| {
+        c != '_'
+            || (i > 0
+                && chars[i - 1].is_digit(radix)
+                && chars.get(i + 1).is_some_and(|next| next.is_digit(radix)))
+    })
+}
+
 /// get the token kind (sans literal) for a piece of text. falls back to "identifier"
 fn token_kind_for_text(text: &str) -> TokenKind {
-    match text.as_ref() {
+    match text {
         "true" => TokenKind::True,
         "false" => TokenKind::False,
         "nil" => TokenKind::Nil,
         "and" => TokenKind::And,
+        "break" => TokenKind::Break,
         "class" => TokenKind::Class,
+        "continue" => TokenKind::Continue,
         "else" => TokenKind::Else,
         "for" => TokenKind::For,
         "fun" => TokenKind::Fun,
         "if" => TokenKind::If,
+        "in" => TokenKind::In,
         "or" => TokenKind::Or,
         "print" => TokenKind::Print,
         "return" => TokenKind::Return,
@@ -228,6 +593,7 @@ mod test {
 
     fn to_token_kinds(source: &str) -> Vec<TokenKind> {
         lex("<for testing>".to_string(), source)
+            .0
             .iter()
             .map(|token| token.kind.clone())
             .collect()
@@ -245,7 +611,326 @@ mod test {
     fn test_var_with_init() {
         assert_eq!(
             to_token_kinds("var a = 1;"),
-            vec![Var, Identifier, Equal, Number(1.0), Semicolon, Eof],
+            vec![Var, Identifier, Equal, Integer(1), Semicolon, Eof],
+        );
+    }
+
+    #[test]
+    fn test_identifiers() {
+        let (tokens, errors) = lex(
+            "<for testing>".to_string(),
+            "my_var _private café Ωmega x\u{301}y 名前 ٣",
+        );
+        assert_eq!(
+            tokens
+                .iter()
+                .map(|token| (token.kind.clone(), token.name()))
+                .collect::<Vec<_>>(),
+            vec![
+                (Identifier, "my_var".to_string()),
+                (Identifier, "_private".to_string()),
+                (Identifier, "café".to_string()),
+                (Identifier, "Ωmega".to_string()),
+                (Identifier, "x\u{301}y".to_string()),
+                (Identifier, "名前".to_string()),
+                (Eof, String::new()),
+            ],
+        );
+        // non-ASCII digits are neither numbers nor identifiers
+        assert_eq!(errors[0].message, "Unexpected character '٣'.");
+    }
+
+    #[test]
+    fn test_columns() {
+        let (tokens, _) = lex("<for testing>".to_string(), "é = \"a\nbc\" + 1;\n\tvar ü;");
+        assert_eq!(
+            tokens
+                .iter()
+                .map(|token| (token.line, token.column))
+                .collect::<Vec<_>>(),
+            vec![
+                (1, 1),
+                (1, 3),
+                (1, 5),
+                (2, 5),
+                (2, 7),
+                (2, 8),
+                (3, 2),
+                (3, 6),
+                (3, 7),
+                (3, 8),
+            ],
+        );
+    }
+
+    #[test]
+    fn test_big_integer() {
+        assert_eq!(
+            to_token_kinds("18446744073709551616"),
+            vec![
+                BigInt(crate::bigint::BigInt::parse("18446744073709551616").unwrap()),
+                Eof
+            ],
+        );
+    }
+
+    /// lex a single number literal, returning its token kind and span
+    fn lex_number(source: &str) -> (TokenKind, Range) {
+        let (tokens, errors) = lex("<for testing>".to_string(), source);
+        assert!(errors.is_empty(), "{:?}", errors);
+        (tokens[0].kind.clone(), tokens[0].span.clone())
+    }
+
+    fn number_error(source: &str) -> (Range, String) {
+        let (_, mut errors) = lex("<for testing>".to_string(), source);
+        let error = errors.remove(0);
+        (error.span, error.message)
+    }
+
+    #[test]
+    fn test_number_forms() {
+        assert_eq!(lex_number("0xFF;"), (Integer(255), Range(0, 4)));
+        assert_eq!(lex_number("0b1010"), (Integer(10), Range(0, 6)));
+        assert_eq!(lex_number("0o755"), (Integer(493), Range(0, 5)));
+        assert_eq!(lex_number("1.5e-3"), (Number(1.5e-3), Range(0, 6)));
+        assert_eq!(lex_number("2E+2"), (Number(200.0), Range(0, 4)));
+        assert_eq!(lex_number("1e3"), (Number(1000.0), Range(0, 3)));
+        assert_eq!(lex_number("1_000_000"), (Integer(1_000_000), Range(0, 9)));
+        assert_eq!(
+            lex_number("0xdead_beef"),
+            (Integer(0xdead_beef), Range(0, 11))
+        );
+        assert_eq!(lex_number("12.345_678"), (Number(12.345_678), Range(0, 10)));
+        assert_eq!(
+            lex_number("0x1_0000_0000_0000_0000"),
+            (
+                BigInt(crate::bigint::BigInt::parse("18446744073709551616").unwrap()),
+                Range(0, 23)
+            )
+        );
+        // a dot not followed by a digit is a method call, not a fraction
+        assert_eq!(
+            to_token_kinds("1.abs"),
+            vec![Integer(1), Dot, Identifier, Eof]
+        );
+    }
+
+    #[test]
+    fn test_malformed_numbers() {
+        let error = |span: Range, message: &str| (span, message.to_string());
+        assert_eq!(
+            number_error("0x;"),
+            error(Range(0, 2), "Missing digits after '0x'.")
+        );
+        assert_eq!(
+            number_error("0b102"),
+            error(Range(0, 5), "Invalid digit '2' in binary literal.")
+        );
+        assert_eq!(
+            number_error("1e;"),
+            error(Range(0, 2), "Missing digits in exponent.")
+        );
+        assert_eq!(
+            number_error("1.5e-"),
+            error(Range(0, 5), "Missing digits in exponent.")
+        );
+        assert_eq!(
+            number_error("1__0 1_"),
+            error(Range(0, 4), "Digit separator '_' must be between digits.")
+        );
+        assert_eq!(
+            number_error("0x_1"),
+            error(Range(0, 4), "Digit separator '_' must be between digits.")
+        );
+    }
+
+    #[test]
+    fn test_string_escapes() {
+        assert_eq!(
+            to_token_kinds(r#""a\tb\n\r \\ \" \0 \x41 \u{1F600} \u{e9}""#),
+            vec![Str("a\tb\n\r \\ \" \0 A \u{1F600} \u{e9}".to_string()), Eof],
+        );
+    }
+
+    #[test]
+    fn test_bad_escapes() {
+        let errors = |source: &str| {
+            lex("<for testing>".to_string(), source)
+                .1
+                .into_iter()
+                .map(|error| (error.line, error.column, error.span, error.message))
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(
+            errors("\"ok\"; \"a\\qb\\x4\""),
+            vec![
+                (
+                    1,
+                    9,
+                    Range(8, 10),
+                    "Unknown escape sequence '\\q'.".to_string()
+                ),
+                (
+                    1,
+                    12,
+                    Range(11, 14),
+                    "Expected two hex digits after '\\x'.".to_string()
+                ),
+            ],
+        );
+        assert_eq!(
+            errors("\"\\u{D800}\" \"\\u00e9\""),
+            vec![
+                (
+                    1,
+                    2,
+                    Range(1, 9),
+                    "'D800' is not a valid unicode character.".to_string()
+                ),
+                (
+                    1,
+                    13,
+                    Range(12, 14),
+                    "Expected '{' after '\\u'.".to_string()
+                ),
+            ],
+        );
+        // an escaped newline still counts as a line
+        assert_eq!(
+            errors("\"a\\\nb\"\nx;\n\"\\q\""),
+            vec![
+                (
+                    1,
+                    3,
+                    Range(2, 4),
+                    "Unknown escape sequence '\\\\n'.".to_string()
+                ),
+                (
+                    4,
+                    2,
+                    Range(11, 13),
+                    "Unknown escape sequence '\\q'.".to_string()
+                ),
+            ],
+        );
+        let tokens = lex("<for testing>".to_string(), "\"a\\\nb\"\nx").0;
+        let x = tokens
+            .iter()
+            .find(|token| token.kind == Identifier)
+            .unwrap();
+        assert_eq!((x.line, x.column), (3, 1));
+    }
+
+    #[test]
+    fn test_interpolation() {
+        assert_eq!(
+            to_token_kinds(r#""a${x}b${ {"k" "${y}"} }c" "\${z}""#),
+            vec![
+                Interpolation("a".to_string()),
+                Identifier,
+                Interpolation("b".to_string()),
+                LeftBrace,
+                Str("k".to_string()),
+                Interpolation(String::new()),
+                Identifier,
+                Str(String::new()),
+                RightBrace,
+                Str("c".to_string()),
+                Str("${z}".to_string()),
+                Eof,
+            ],
+        );
+        let (_, errors) = lex("<for testing>".to_string(), "\"a${b");
+        assert_eq!(errors[0].message, "Unterminated string interpolation.");
+    }
+
+    #[test]
+    fn test_comments() {
+        let (tokens, errors) = lex(
+            "<for testing>".to_string(),
+            "a /* one\n /* two\n */ still */ b // c\n/// doc\n////not doc\nd /* open\n",
+        );
+        assert_eq!(
+            tokens
+                .iter()
+                .map(|token| (token.kind.clone(), token.line))
+                .collect::<Vec<_>>(),
+            vec![
+                (Identifier, 1),
+                (Identifier, 3),
+                (DocComment("doc".to_string()), 4),
+                (Identifier, 6),
+                (Eof, 7),
+            ],
+        );
+        assert_eq!(
+            errors
+                .iter()
+                .map(|error| (error.line, error.column, error.message.as_str()))
+                .collect::<Vec<_>>(),
+            vec![(6, 3, "Unterminated block comment.")],
+        );
+    }
+
+    #[test]
+    fn test_multi_line_string() {
+        let (tokens, errors) = lex("<for testing>".to_string(), "\"a\nb\" x\n\"\\q\"");
+        assert_eq!(tokens[0].kind, Str("a\nb".to_string()));
+        assert_eq!(tokens[0].line, 1);
+        assert_eq!(tokens[1].line, 2);
+        assert_eq!((errors[0].line, errors[0].column), (3, 2));
+    }
+
+    #[test]
+    fn test_errors_keep_scanning() {
+        let (tokens, errors) = lex("<for testing>".to_string(), "var @a;\n  # \"open");
+        assert_eq!(
+            tokens
+                .iter()
+                .map(|token| token.kind.clone())
+                .collect::<Vec<_>>(),
+            vec![Var, Identifier, Semicolon, Eof],
+        );
+        assert_eq!(
+            errors
+                .iter()
+                .map(|error| (error.line, error.column, error.message.as_str()))
+                .collect::<Vec<_>>(),
+            vec![
+                (1, 5, "Unexpected character '@'."),
+                (2, 3, "Unexpected character '#'."),
+                (2, 5, "Unterminated string."),
+            ],
+        );
+    }
+
+    #[test]
+    fn test_ranges() {
+        assert_eq!(
+            to_token_kinds("0..10 1..=n a.b"),
+            vec![
+                Integer(0),
+                DotDot,
+                Integer(10),
+                Integer(1),
+                DotDotEqual,
+                Identifier,
+                Identifier,
+                Dot,
+                Identifier,
+                Eof,
+            ],
+        );
+    }
+
+    #[test]
+    fn test_arithmetic_operators() {
+        assert_eq!(
+            to_token_kinds("a * b ** c % d / e ~/ f // comment"),
+            vec![
+                Identifier, Star, Identifier, StarStar, Identifier, Percent, Identifier, Slash,
+                Identifier, TildeSlash, Identifier, Eof,
+            ],
         );
     }
 }
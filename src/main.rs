@@ -1,43 +1,88 @@
+use diagnostic::Diagnostic;
+use interpret::Interpreter;
 use std::env;
 use std::fs;
 use std::io::{self, prelude::Write};
 
+mod bigint;
+mod class;
+mod diagnostic;
 mod environment;
+mod function;
 mod interpret;
 mod lex;
+mod map;
+mod math;
+mod native;
 mod parse;
+mod resolve;
 mod stmt;
 mod token;
+mod unicode;
+
+const USAGE: &str = "usage: lox [--error-format=human|json] [filename]";
 
 fn main() {
-    let mut lox = Lox::new();
-    let args: Vec<String> = env::args().skip(1).collect();
-    match args.as_slice() {
-        [] => lox.run_prompt(),
-        [script_name] => lox.run_file(script_name),
-        _ => println!("usage: lox [filename]"),
+    let mut error_format = ErrorFormat::Human;
+    let mut args = Vec::new();
+    for arg in env::args().skip(1) {
+        match arg.strip_prefix("--error-format=") {
+            Some("human") => error_format = ErrorFormat::Human,
+            Some("json") => error_format = ErrorFormat::Json,
+            Some(_) => {
+                println!("{}", USAGE);
+                std::process::exit(64);
+            }
+            None => args.push(arg),
+        }
     }
+
+    // the interpreter recurses for every call, so it gets a thread with a bigger stack
+    std::thread::Builder::new()
+        .stack_size(interpret::STACK_SIZE)
+        .spawn(move || {
+            let mut lox = Lox::new(error_format);
+            match args.as_slice() {
+                [] => lox.run_prompt(),
+                [script_name] => lox.run_file(script_name),
+                _ => println!("{}", USAGE),
+            }
+        })
+        .expect("failed to start the interpreter thread")
+        .join()
+        .expect("the interpreter panicked");
+}
+
+/// how errors are printed to stderr
+enum ErrorFormat {
+    /// source snippets with the problem underlined
+    Human,
+    /// one JSON object per line, for editors and CI
+    Json,
 }
 
 struct Lox {
     had_error: bool,
     had_runtime_error: bool,
+    error_format: ErrorFormat,
 }
 
 impl Lox {
-    fn new() -> Self {
+    fn new(error_format: ErrorFormat) -> Self {
         Lox {
             had_error: false,
             had_runtime_error: false,
+            error_format,
         }
     }
 
     /// run the interpreter on a file
-    fn run_file(&self, script_name: &str) {
-        let contents =
-            fs::read_to_string(script_name).expect(&format!("could not open {}", script_name));
+    fn run_file(&mut self, script_name: &str) {
+        let contents = fs::read_to_string(script_name)
+            .unwrap_or_else(|_| panic!("could not open {}", script_name));
 
-        self.run(script_name.to_string(), &contents, None);
+        let mut interpreter = Interpreter::new();
+        self.run(script_name.to_string(), &contents, &mut interpreter);
 
         if self.had_error {
             std::process::exit(65);
@@ -51,7 +96,7 @@ impl Lox {
         let stdin = io::stdin();
         let mut stdout = io::stdout();
         let mut buf = String::new();
-        let mut environment = environment::Environment::new();
+        let mut interpreter = Interpreter::new();
         loop {
             write!(stdout, "> ").expect("unable to write to stdout");
             stdout.flush().expect("failed to flush stdout");
@@ -60,21 +105,50 @@ impl Lox {
                 .read_line(&mut buf)
                 .expect("failed to read line from stdin");
 
-            environment = self.run("<repl>".to_string(), &buf, Some(environment));
+            self.run("<repl>".to_string(), &buf, &mut interpreter);
             self.had_error = false;
+            self.had_runtime_error = false;
 
             buf.clear();
         }
     }
 
-    fn run(
-        &self,
-        name: String,
-        source: &str,
-        environment: Option<environment::Environment>,
-    ) -> environment::Environment {
-        let tokens = lex::lex(name, source);
-        let statements = parse::parse(tokens);
-        interpret::interpret(statements, environment)
+    fn run(&mut self, name: String, source: &str, interpreter: &mut Interpreter) {
+        let (tokens, errors) = lex::lex(name, source);
+        if !errors.is_empty() {
+            self.report(errors);
+            return;
+        }
+
+        let (statements, errors) = parse::parse(tokens);
+        if !errors.is_empty() {
+            self.report(errors);
+            return;
+        }
+
+        if let Err(errors) = resolve::resolve(&statements) {
+            self.report(errors);
+            return;
+        }
+
+        if let Err(error) = interpreter.interpret(statements) {
+            self.print_diagnostic(error.into());
+            self.had_runtime_error = true;
+        }
+    }
+
+    /// print compile-time errors
+    fn report<E: Into<Diagnostic>>(&mut self, errors: Vec<E>) {
+        for error in errors {
+            self.print_diagnostic(error.into());
+        }
+        self.had_error = true;
+    }
+
+    fn print_diagnostic(&self, diagnostic: Diagnostic) {
+        match self.error_format {
+            ErrorFormat::Human => eprintln!("{}", diagnostic),
+            ErrorFormat::Json => eprintln!("{}", diagnostic.to_json()),
+        }
     }
 }
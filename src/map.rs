@@ -0,0 +1,164 @@
+use crate::bigint::BigInt;
+use crate::token::Literal;
+use std::collections::HashMap;
+use std::hash::{Hash, Hasher};
+use std::rc::Rc;
+
+/// a map that remembers the order its keys were first inserted in
+#[derive(Debug, Default)]
+pub struct LoxMap {
+    entries: Vec<(Literal, Literal)>,
+    indices: HashMap<MapKey, usize>,
+}
+
+impl LoxMap {
+    /// the value stored under `key`, if any
+    pub fn get(&self, key: &Literal) -> Option<&Literal> {
+        let index = self.indices.get(&MapKey::new(key.clone())?)?;
+        Some(&self.entries[*index].1)
+    }
+
+    /// insert or update an entry. a new key goes at the end, an existing key keeps its place.
+    /// fails if the key is nan, which could never be looked up again
+    pub fn insert(&mut self, key: Literal, value: Literal) -> Result<(), ()> {
+        let key = MapKey::new(key).ok_or(())?;
+        match self.indices.get(&key) {
+            Some(index) => self.entries[*index].1 = value,
+            None => {
+                self.indices.insert(key.clone(), self.entries.len());
+                self.entries.push((key.0, value));
+            }
+        }
+        Ok(())
+    }
+
+    pub fn remove(&mut self, key: &Literal) -> Option<Literal> {
+        let index = self.indices.remove(&MapKey::new(key.clone())?)?;
+        // keeping the order means shifting everything after the removed entry down
+        for later in self.indices.values_mut() {
+            if *later > index {
+                *later -= 1;
+            }
+        }
+        Some(self.entries.remove(index).1)
+    }
+
+    /// the entries in insertion order
+    pub fn iter(&self) -> impl ExactSizeIterator<Item = (&Literal, &Literal)> {
+        self.entries.iter().map(|(key, value)| (key, value))
+    }
+}
+
+/// a key as it's stored in a map, hashing and comparing the same way as `Literal`'s
+/// `PartialEq`
+///
+/// numbers compare by value, so every integral number is stored as an integer, e.g. `1.0`
+/// and `-0.0` are the keys `1` and `0`. other floats are keys as they are, and nan can't be a
+/// key at all, as it isn't equal to itself. ranges are keyed by value, and functions, natives,
+/// classes, instances, lists and maps by identity
+#[derive(Clone, Debug)]
+struct MapKey(Literal);
+
+impl MapKey {
+    fn new(key: Literal) -> Option<Self> {
+        match key {
+            Literal::Number(num) if num.is_nan() => None,
+            Literal::Number(num) => Some(MapKey(match BigInt::from_f64(num) {
+                Some(num) => num.into(),
+                None => key,
+            })),
+            key => Some(MapKey(key)),
+        }
+    }
+}
+
+impl PartialEq for MapKey {
+    fn eq(&self, other: &MapKey) -> bool {
+        self.0 == other.0
+    }
+}
+
+impl Eq for MapKey {}
+
+impl Hash for MapKey {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        // equal keys always have the same variant, since numbers have been normalized
+        std::mem::discriminant(&self.0).hash(state);
+        match &self.0 {
+            Literal::Bool(val) => val.hash(state),
+            Literal::Nil => {}
+            Literal::Number(num) => num.to_bits().hash(state),
+            Literal::Integer(num) => num.hash(state),
+            Literal::BigInt(num) => num.hash(state),
+            Literal::Str(text) => text.hash(state),
+            Literal::Function(function) => Rc::as_ptr(function).hash(state),
+            Literal::Native(native) => Rc::as_ptr(native).hash(state),
+            Literal::Class(class) => Rc::as_ptr(class).hash(state),
+            Literal::Instance(instance) => Rc::as_ptr(instance).hash(state),
+            Literal::List(list) => Rc::as_ptr(list).hash(state),
+            Literal::Map(map) => Rc::as_ptr(map).hash(state),
+            Literal::Range {
+                start,
+                end,
+                inclusive,
+            } => (start, end, inclusive).hash(state),
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_numeric_keys() {
+        let mut map = LoxMap::default();
+        map.insert(Literal::Integer(1), Literal::Str("one".to_string()))
+            .unwrap();
+        map.insert(Literal::Number(-0.0), Literal::Str("zero".to_string()))
+            .unwrap();
+        map.insert(Literal::Number(0.5), Literal::Str("half".to_string()))
+            .unwrap();
+
+        assert_eq!(
+            map.get(&Literal::Number(1.0)),
+            Some(&Literal::Str("one".to_string()))
+        );
+        assert_eq!(
+            map.get(&Literal::Integer(0)),
+            Some(&Literal::Str("zero".to_string()))
+        );
+        assert_eq!(
+            map.get(&Literal::Number(0.5)),
+            Some(&Literal::Str("half".to_string()))
+        );
+        assert_eq!(map.get(&Literal::Str("1".to_string())), None);
+        assert_eq!(map.get(&Literal::Number(f64::NAN)), None);
+        assert!(map.insert(Literal::Number(f64::NAN), Literal::Nil).is_err());
+    }
+
+    #[test]
+    fn test_insertion_order() {
+        let mut map = LoxMap::default();
+        for key in ["c", "a", "b"] {
+            map.insert(Literal::Str(key.to_string()), Literal::Nil)
+                .unwrap();
+        }
+        map.insert(Literal::Str("c".to_string()), Literal::Bool(true))
+            .unwrap();
+        assert_eq!(
+            map.remove(&Literal::Str("a".to_string())),
+            Some(Literal::Nil)
+        );
+        map.insert(Literal::Str("a".to_string()), Literal::Nil)
+            .unwrap();
+
+        let keys: Vec<_> = map.iter().map(|(key, _)| key.to_string()).collect();
+        assert_eq!(keys, ["c", "b", "a"]);
+        assert_eq!(
+            map.get(&Literal::Str("c".to_string())),
+            Some(&Literal::Bool(true))
+        );
+        assert_eq!(map.remove(&Literal::Str("x".to_string())), None);
+    }
+}
@@ -0,0 +1,167 @@
+use crate::bigint::BigInt;
+use crate::interpret::{Interpreter, RuntimeError};
+use crate::native::NativeFunction;
+use crate::token::Literal;
+use std::cmp::Ordering;
+use std::f64::consts;
+
+pub const CONSTANTS: [(&str, f64); 4] = [
+    ("pi", consts::PI),
+    ("e", consts::E),
+    ("inf", f64::INFINITY),
+    ("nan", f64::NAN),
+];
+
+/// the math functions. most of them work on floats and return floats, whatever numbers they're
+/// given. `abs`, `min` and `max` keep integers as integers, and `floor`, `ceil` and `round`
+/// return integers unless the result is infinite or nan
+pub fn functions() -> Vec<NativeFunction> {
+    vec![
+        NativeFunction::new("sqrt", Some(1), |interpreter, arguments| {
+            float_function(interpreter, "sqrt", &arguments, f64::sqrt)
+        }),
+        NativeFunction::new("pow", Some(2), |interpreter, arguments| {
+            let base = number(interpreter, "pow", &arguments, 0)?;
+            let exponent = number(interpreter, "pow", &arguments, 1)?;
+            Ok(Literal::Number(base.powf(exponent)))
+        }),
+        NativeFunction::new("abs", Some(1), abs),
+        NativeFunction::new("floor", Some(1), |interpreter, arguments| {
+            rounding_function(interpreter, "floor", &arguments, f64::floor)
+        }),
+        NativeFunction::new("ceil", Some(1), |interpreter, arguments| {
+            rounding_function(interpreter, "ceil", &arguments, f64::ceil)
+        }),
+        // halfway cases round away from zero
+        NativeFunction::new("round", Some(1), |interpreter, arguments| {
+            rounding_function(interpreter, "round", &arguments, f64::round)
+        }),
+        NativeFunction::new("min", None, |interpreter, arguments| {
+            extreme(interpreter, "min", arguments, Ordering::Less)
+        }),
+        NativeFunction::new("max", None, |interpreter, arguments| {
+            extreme(interpreter, "max", arguments, Ordering::Greater)
+        }),
+        NativeFunction::new("sin", Some(1), |interpreter, arguments| {
+            float_function(interpreter, "sin", &arguments, f64::sin)
+        }),
+        NativeFunction::new("cos", Some(1), |interpreter, arguments| {
+            float_function(interpreter, "cos", &arguments, f64::cos)
+        }),
+        NativeFunction::new("tan", Some(1), |interpreter, arguments| {
+            float_function(interpreter, "tan", &arguments, f64::tan)
+        }),
+        NativeFunction::new("atan2", Some(2), |interpreter, arguments| {
+            let y = number(interpreter, "atan2", &arguments, 0)?;
+            let x = number(interpreter, "atan2", &arguments, 1)?;
+            Ok(Literal::Number(y.atan2(x)))
+        }),
+        // the natural logarithm
+        NativeFunction::new("log", Some(1), |interpreter, arguments| {
+            float_function(interpreter, "log", &arguments, f64::ln)
+        }),
+        NativeFunction::new("exp", Some(1), |interpreter, arguments| {
+            float_function(interpreter, "exp", &arguments, f64::exp)
+        }),
+        NativeFunction::new("is_nan", Some(1), |interpreter, arguments| {
+            let num = number(interpreter, "is_nan", &arguments, 0)?;
+            Ok(Literal::Bool(num.is_nan()))
+        }),
+    ]
+}
+
+/// the argument at `index` as a float, or an error naming the function and the argument
+fn number(
+    interpreter: &Interpreter,
+    name: &str,
+    arguments: &[Literal],
+    index: usize,
+) -> Result<f64, RuntimeError> {
+    let argument = &arguments[index];
+    argument.as_float().ok_or_else(|| {
+        interpreter.native_error(
+            "runtime-argument-type",
+            format!(
+                "{}() expects a number as argument {}, not a {}.",
+                name,
+                index + 1,
+                argument.kind_name()
+            ),
+        )
+    })
+}
+
+fn float_function(
+    interpreter: &Interpreter,
+    name: &str,
+    arguments: &[Literal],
+    function: fn(f64) -> f64,
+) -> Result<Literal, RuntimeError> {
+    Ok(Literal::Number(function(number(
+        interpreter,
+        name,
+        arguments,
+        0,
+    )?)))
+}
+
+fn rounding_function(
+    interpreter: &Interpreter,
+    name: &str,
+    arguments: &[Literal],
+    round: fn(f64) -> f64,
+) -> Result<Literal, RuntimeError> {
+    let num = number(interpreter, name, arguments, 0)?;
+    Ok(match &arguments[0] {
+        Literal::Number(_) => {
+            let rounded = round(num);
+            match BigInt::from_f64(rounded) {
+                Some(int) => int.into(),
+                None => Literal::Number(rounded),
+            }
+        }
+        // integers are already rounded
+        integer => integer.clone(),
+    })
+}
+
+fn abs(interpreter: &mut Interpreter, arguments: Vec<Literal>) -> Result<Literal, RuntimeError> {
+    let num = number(interpreter, "abs", &arguments, 0)?;
+    Ok(match &arguments[0] {
+        Literal::Integer(int) => match int.checked_abs() {
+            Some(int) => Literal::Integer(int),
+            None => (-&BigInt::from(*int)).into(),
+        },
+        Literal::BigInt(big) if big.is_negative() => (-big).into(),
+        Literal::BigInt(big) => Literal::BigInt(big.clone()),
+        _ => Literal::Number(num.abs()),
+    })
+}
+
+/// the smallest or largest of the arguments, or nan if any of them is nan
+fn extreme(
+    interpreter: &Interpreter,
+    name: &str,
+    arguments: Vec<Literal>,
+    wanted: Ordering,
+) -> Result<Literal, RuntimeError> {
+    if arguments.is_empty() {
+        return Err(interpreter.native_error(
+            "runtime-arity",
+            format!("{}() expects at least 1 argument.", name),
+        ));
+    }
+    for index in 0..arguments.len() {
+        number(interpreter, name, &arguments, index)?;
+    }
+
+    let mut best = arguments[0].clone();
+    for argument in arguments {
+        match argument.compare_number(&best) {
+            None => return Ok(Literal::Number(f64::NAN)),
+            Some(ordering) if ordering == wanted => best = argument,
+            Some(_) => {}
+        }
+    }
+    Ok(best)
+}
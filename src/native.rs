@@ -0,0 +1,66 @@
+use crate::interpret::{Interpreter, RuntimeError};
+use crate::math;
+use crate::token::Literal;
+use std::fmt;
+use std::rc::Rc;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// a function implemented in rust
+pub struct NativeFunction {
+    pub name: &'static str,
+    /// `None` for variadic functions, which check their arguments themselves
+    pub arity: Option<usize>,
+    pub function: fn(&mut Interpreter, Vec<Literal>) -> Result<Literal, RuntimeError>,
+}
+
+impl NativeFunction {
+    pub fn new(
+        name: &'static str,
+        arity: Option<usize>,
+        function: fn(&mut Interpreter, Vec<Literal>) -> Result<Literal, RuntimeError>,
+    ) -> Self {
+        Self {
+            name,
+            arity,
+            function,
+        }
+    }
+}
+
+/// natives are only ever equal to themselves
+impl PartialEq for NativeFunction {
+    fn eq(&self, other: &NativeFunction) -> bool {
+        std::ptr::eq(self, other)
+    }
+}
+
+impl fmt::Debug for NativeFunction {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "<native fn {}>", self.name)
+    }
+}
+
+/// the names and values defined in every program's global scope
+pub fn prelude() -> Vec<(&'static str, Literal)> {
+    let mut natives = vec![NativeFunction::new("clock", Some(0), clock)];
+    natives.extend(math::functions());
+
+    let mut prelude: Vec<_> = natives
+        .into_iter()
+        .map(|native| (native.name, Literal::Native(Rc::new(native))))
+        .collect();
+    prelude.extend(
+        math::CONSTANTS
+            .iter()
+            .map(|&(name, value)| (name, Literal::Number(value))),
+    );
+    prelude
+}
+
+/// seconds since the unix epoch, for timing things
+fn clock(_: &mut Interpreter, _: Vec<Literal>) -> Result<Literal, RuntimeError> {
+    let now = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .expect("the system clock is set before 1970");
+    Ok(Literal::Number(now.as_secs_f64()))
+}
@@ -1,19 +1,58 @@
-use crate::stmt::{Expr, Stmt};
+use crate::stmt::{Depth, Expr, FunctionDecl, Stmt};
 use crate::token::{Literal, Token, TokenKind};
+use std::collections::HashMap;
+use std::rc::Rc;
 
-pub fn parse(tokens: Vec<Token>) -> Vec<Stmt> {
-    Parser { tokens, current: 0 }.parse()
+/// parse tokens into statements, recovering after errors so that they can all be reported at
+/// once. the statements are only meaningful if there were no errors
+pub fn parse(tokens: Vec<Token>) -> (Vec<Stmt>, Vec<ParseErr>) {
+    // doc comments aren't part of the grammar, so set them aside, keyed by the token after them
+    let mut docs = HashMap::new();
+    let mut lines = Vec::new();
+    let mut rest = Vec::new();
+    for token in tokens {
+        match token.kind {
+            TokenKind::DocComment(text) => lines.push(text),
+            _ => {
+                if !lines.is_empty() {
+                    docs.insert(rest.len(), lines.join("\n"));
+                    lines.clear();
+                }
+                rest.push(token);
+            }
+        }
+    }
+
+    Parser {
+        tokens: rest,
+        current: 0,
+        docs,
+        loops: Vec::new(),
+        errors: Vec::new(),
+    }
+    .parse()
 }
 
+#[derive(Debug)]
 pub struct ParseErr {
-    token: Token,
-    message: String,
+    /// stable identifier for the kind of error, see `Diagnostic::code`
+    pub code: &'static str,
+    pub token: Token,
+    pub message: String,
 }
 
 #[derive(Debug)]
 struct Parser {
     tokens: Vec<Token>,
     current: usize,
+    /// doc comments by the index of the token they come before. any not claimed by a
+    /// declaration are ignored
+    docs: HashMap<usize, String>,
+    /// labels of the loops enclosing the current statement, innermost last, within the current
+    /// function
+    loops: Vec<Option<Token>>,
+    /// includes errors that didn't stop the statement being parsed, so weren't returned
+    errors: Vec<ParseErr>,
 }
 
 macro_rules! check {
@@ -44,11 +83,12 @@ macro_rules! did_eat {
 }
 
 macro_rules! consume {
-    ($self:ident, $p:pat, $message:literal) => {
+    ($self:ident, $p:pat, $code:literal, $message:literal) => {
         if let Some(tok) = eat!($self, $p) {
             Ok(tok)
         } else {
             Err(ParseErr {
+                code: $code,
                 message: $message.to_string(),
                 token: $self.peek(),
             })
@@ -57,32 +97,158 @@ macro_rules! consume {
 }
 
 impl Parser {
-    fn parse(&mut self) -> Vec<Stmt> {
+    fn parse(&mut self) -> (Vec<Stmt>, Vec<ParseErr>) {
         let mut statments = Vec::new();
 
         while !self.is_at_end() {
             match self.declaration() {
                 Ok(statement) => statments.push(statement),
-                Err(ParseErr { message, token }) => {
+                Err(error) => {
                     self.synchronize();
-                    println!("parse error at {:?}: {}", token.kind, message)
+                    self.errors.push(error);
                 }
             }
         }
 
-        statments
+        (statments, std::mem::take(&mut self.errors))
     }
 
     fn declaration(&mut self) -> Result<Stmt, ParseErr> {
-        if eat!(self, TokenKind::Var).is_some() {
-            self.var_declaration()
+        let doc = self.docs.remove(&self.current);
+        if did_eat!(self, TokenKind::Class) {
+            self.class_declaration(doc)
+        } else if did_eat!(self, TokenKind::Fun) {
+            Ok(Stmt::Function {
+                decl: Rc::new(self.function(doc)?),
+            })
+        } else if eat!(self, TokenKind::Var).is_some() {
+            self.var_declaration(doc)
         } else {
             self.statement()
         }
     }
 
-    fn var_declaration(&mut self) -> Result<Stmt, ParseErr> {
-        let name = consume!(self, TokenKind::Identifier, "Expect variable name.")?;
+    fn class_declaration(&mut self, doc: Option<String>) -> Result<Stmt, ParseErr> {
+        let name = consume!(
+            self,
+            TokenKind::Identifier,
+            "parse-expect-name",
+            "Expect class name."
+        )?;
+
+        let superclass = if did_eat!(self, TokenKind::Less) {
+            let superclass = consume!(
+                self,
+                TokenKind::Identifier,
+                "parse-expect-name",
+                "Expect superclass name."
+            )?;
+            if superclass.name() == name.name() {
+                return Err(ParseErr {
+                    code: "parse-inherit-from-itself",
+                    token: superclass,
+                    message: "A class can't inherit from itself.".to_string(),
+                });
+            }
+            Some(Expr::Variable {
+                name: superclass,
+                depth: Depth::default(),
+            })
+        } else {
+            None
+        };
+
+        consume!(
+            self,
+            TokenKind::LeftBrace,
+            "parse-expect-left-brace",
+            "Expect '{' before class body."
+        )?;
+
+        let mut methods = Vec::new();
+        while !check!(self, TokenKind::RightBrace) && !self.is_at_end() {
+            let doc = self.docs.remove(&self.current);
+            methods.push(Rc::new(self.function(doc)?));
+        }
+        consume!(
+            self,
+            TokenKind::RightBrace,
+            "parse-expect-right-brace",
+            "Expect '}' after class body."
+        )?;
+
+        Ok(Stmt::Class {
+            name,
+            superclass,
+            methods,
+            doc,
+        })
+    }
+
+    /// parses a function's name, parameters and body. used for both `fun` declarations and
+    /// methods, which are written without the `fun` keyword
+    fn function(&mut self, doc: Option<String>) -> Result<FunctionDecl, ParseErr> {
+        let name = consume!(
+            self,
+            TokenKind::Identifier,
+            "parse-expect-name",
+            "Expect function name."
+        )?;
+        consume!(
+            self,
+            TokenKind::LeftParen,
+            "parse-expect-left-paren",
+            "Expect '(' after function name."
+        )?;
+
+        let mut params = Vec::new();
+        if !check!(self, TokenKind::RightParen) {
+            loop {
+                params.push(consume!(
+                    self,
+                    TokenKind::Identifier,
+                    "parse-expect-name",
+                    "Expect parameter name."
+                )?);
+                if !did_eat!(self, TokenKind::Comma) {
+                    break;
+                }
+            }
+        }
+        consume!(
+            self,
+            TokenKind::RightParen,
+            "parse-expect-right-paren",
+            "Expect ')' after parameters."
+        )?;
+
+        consume!(
+            self,
+            TokenKind::LeftBrace,
+            "parse-expect-left-brace",
+            "Expect '{' before function body."
+        )?;
+        // loops outside the function can't be broken out of from inside it
+        let enclosing_loops = std::mem::take(&mut self.loops);
+        let body = self.block();
+        self.loops = enclosing_loops;
+        let body = body?;
+
+        Ok(FunctionDecl {
+            name,
+            params,
+            body,
+            doc,
+        })
+    }
+
+    fn var_declaration(&mut self, doc: Option<String>) -> Result<Stmt, ParseErr> {
+        let name = consume!(
+            self,
+            TokenKind::Identifier,
+            "parse-expect-name",
+            "Expect variable name."
+        )?;
 
         let initializer = if eat!(self, TokenKind::Equal).is_some() {
             Some(self.expression()?)
@@ -93,22 +259,34 @@ impl Parser {
         consume!(
             self,
             TokenKind::Semicolon,
+            "parse-expect-semicolon",
             "Expect ';' after variable declaration."
         )?;
 
-        Ok(Stmt::Var { name, initializer })
+        Ok(Stmt::Var {
+            name,
+            initializer,
+            doc,
+        })
     }
 
     fn statement(&mut self) -> Result<Stmt, ParseErr> {
-        if did_eat!(self, TokenKind::For) {
-            self.for_statement()
+        if check!(self, TokenKind::Identifier) && self.peek_nth(1).kind == TokenKind::Colon {
+            self.labeled_statement()
+        } else if did_eat!(self, TokenKind::For) {
+            self.for_statement(None)
         } else if did_eat!(self, TokenKind::If) {
             self.if_statement()
         } else if did_eat!(self, TokenKind::Print) {
             self.print_statement()
+        } else if let Some(keyword) = eat!(self, TokenKind::Return) {
+            self.return_statement(keyword)
         } else if did_eat!(self, TokenKind::While) {
-            self.while_statement()
-        } else if did_eat!(self, TokenKind::LeftBrace) {
+            self.while_statement(None)
+        } else if let Some(keyword) = eat!(self, TokenKind::Break, TokenKind::Continue) {
+            self.loop_control_statement(keyword)
+        } else if check!(self, TokenKind::LeftBrace) && !self.starts_map() {
+            self.advance();
             Ok(Stmt::Block {
                 statements: self.block()?,
             })
@@ -117,14 +295,73 @@ impl Parser {
         }
     }
 
-    /// for statements are de-sugared into while loops
-    fn for_statement(&mut self) -> Result<Stmt, ParseErr> {
-        consume!(self, TokenKind::LeftParen, "Expect '(' after 'for'.")?;
+    /// at the start of a statement, `{` opens a block unless it's followed by a one-token key
+    /// and a colon, e.g. `{"a": 1}.keys();`. other maps need parentheses there
+    fn starts_map(&self) -> bool {
+        let key = self.peek_nth(1);
+        if matches!(key.kind, TokenKind::Eof | TokenKind::RightBrace)
+            || self.peek_nth(2).kind != TokenKind::Colon
+        {
+            return false;
+        }
+        // `{ outer: while (...) ... }` is a block starting with a labeled loop
+        key.kind != TokenKind::Identifier
+            || !matches!(self.peek_nth(3).kind, TokenKind::For | TokenKind::While)
+    }
+
+    /// a loop with a name that `break` and `continue` can refer to, e.g. `outer: while (...)`
+    fn labeled_statement(&mut self) -> Result<Stmt, ParseErr> {
+        let label = self.advance();
+        self.advance();
+
+        if self
+            .loops
+            .iter()
+            .flatten()
+            .any(|outer| outer.name() == label.name())
+        {
+            self.errors.push(ParseErr {
+                code: "parse-duplicate-label",
+                message: format!(
+                    "Label '{}' is already used by an enclosing loop.",
+                    label.name()
+                ),
+                token: label.clone(),
+            });
+        }
+
+        if did_eat!(self, TokenKind::For) {
+            self.for_statement(Some(label))
+        } else if did_eat!(self, TokenKind::While) {
+            self.while_statement(Some(label))
+        } else {
+            Err(ParseErr {
+                code: "parse-expect-loop",
+                token: self.peek(),
+                message: "Expect loop after label.".to_string(),
+            })
+        }
+    }
+
+    /// for statements are de-sugared into while loops, except for `for (var x in xs)`
+    fn for_statement(&mut self, label: Option<Token>) -> Result<Stmt, ParseErr> {
+        consume!(
+            self,
+            TokenKind::LeftParen,
+            "parse-expect-left-paren",
+            "Expect '(' after 'for'."
+        )?;
+        let var = if check!(self, TokenKind::Var) { 1 } else { 0 };
+        if self.peek_nth(var).kind == TokenKind::Identifier
+            && self.peek_nth(var + 1).kind == TokenKind::In
+        {
+            return self.for_in_statement(label);
+        }
 
         let initializer = if did_eat!(self, TokenKind::Semicolon) {
             None
         } else if did_eat!(self, TokenKind::Var) {
-            Some(self.var_declaration()?)
+            Some(self.var_declaration(None)?)
         } else {
             Some(self.expression_statement()?)
         };
@@ -139,6 +376,7 @@ impl Parser {
         consume!(
             self,
             TokenKind::Semicolon,
+            "parse-expect-semicolon",
             "Expect ';' after loop condition."
         )?;
 
@@ -147,18 +385,18 @@ impl Parser {
         } else {
             Some(self.expression()?)
         };
-        consume!(self, TokenKind::RightParen, "Expect ')' after for clauses.")?;
-
-        let mut body = self.statement()?;
-        if let Some(increment) = increment {
-            body = Stmt::Block {
-                statements: vec![body, Stmt::Expression { expr: increment }],
-            };
-        }
+        consume!(
+            self,
+            TokenKind::RightParen,
+            "parse-expect-right-paren",
+            "Expect ')' after for clauses."
+        )?;
 
-        body = Stmt::While {
-            body: Box::new(body),
+        let mut body = Stmt::While {
+            body: Box::new(self.loop_body(label.clone())?),
             condition,
+            increment,
+            label,
         };
 
         if let Some(initializer) = initializer {
@@ -170,12 +408,40 @@ impl Parser {
         Ok(body)
     }
 
+    /// the rest of `for (var x in xs) body`, after the `(`. the `var` is optional, as the loop
+    /// always declares a new variable
+    fn for_in_statement(&mut self, label: Option<Token>) -> Result<Stmt, ParseErr> {
+        did_eat!(self, TokenKind::Var);
+        let name = self.advance();
+        let keyword = self.advance();
+        let iterable = self.expression()?;
+        consume!(
+            self,
+            TokenKind::RightParen,
+            "parse-expect-right-paren",
+            "Expect ')' after for clauses."
+        )?;
+        Ok(Stmt::ForIn {
+            name,
+            keyword,
+            iterable,
+            body: Box::new(self.loop_body(label.clone())?),
+            label,
+        })
+    }
+
     fn if_statement(&mut self) -> Result<Stmt, ParseErr> {
-        consume!(self, TokenKind::LeftParen, "Expect '(' after 'if'.")?;
+        consume!(
+            self,
+            TokenKind::LeftParen,
+            "parse-expect-left-paren",
+            "Expect '(' after 'if'."
+        )?;
         let condition = self.expression()?;
         consume!(
             self,
             TokenKind::RightParen,
+            "parse-expect-right-paren",
             "Expect ')' after if condition."
         )?;
 
@@ -195,22 +461,94 @@ impl Parser {
 
     fn print_statement(&mut self) -> Result<Stmt, ParseErr> {
         let expr = self.expression()?;
-        consume!(self, TokenKind::Semicolon, "Expect ';' after value.")?;
+        consume!(
+            self,
+            TokenKind::Semicolon,
+            "parse-expect-semicolon",
+            "Expect ';' after value."
+        )?;
         Ok(Stmt::Print { expr })
     }
 
-    fn while_statement(&mut self) -> Result<Stmt, ParseErr> {
-        consume!(self, TokenKind::LeftParen, "Expect '(' after 'while'.")?;
+    fn return_statement(&mut self, keyword: Token) -> Result<Stmt, ParseErr> {
+        let value = if check!(self, TokenKind::Semicolon) {
+            None
+        } else {
+            Some(self.expression()?)
+        };
+        consume!(
+            self,
+            TokenKind::Semicolon,
+            "parse-expect-semicolon",
+            "Expect ';' after return value."
+        )?;
+        Ok(Stmt::Return { keyword, value })
+    }
+
+    fn while_statement(&mut self, label: Option<Token>) -> Result<Stmt, ParseErr> {
+        consume!(
+            self,
+            TokenKind::LeftParen,
+            "parse-expect-left-paren",
+            "Expect '(' after 'while'."
+        )?;
         let condition = self.expression()?;
         consume!(
             self,
             TokenKind::RightParen,
+            "parse-expect-right-paren",
             "Expect ')' after while condition."
         )?;
-        let body = self.statement()?;
         Ok(Stmt::While {
             condition,
-            body: Box::new(body),
+            body: Box::new(self.loop_body(label.clone())?),
+            increment: None,
+            label,
+        })
+    }
+
+    fn loop_body(&mut self, label: Option<Token>) -> Result<Stmt, ParseErr> {
+        self.loops.push(label);
+        let body = self.statement();
+        self.loops.pop();
+        body
+    }
+
+    /// `break` or `continue`, whose keyword has already been consumed
+    fn loop_control_statement(&mut self, keyword: Token) -> Result<Stmt, ParseErr> {
+        let label = eat!(self, TokenKind::Identifier);
+
+        // the statement itself is fine, so there's no need to resynchronize
+        if self.loops.is_empty() {
+            self.errors.push(ParseErr {
+                code: "parse-outside-loop",
+                message: format!("Can't use '{}' outside of a loop.", keyword.name()),
+                token: keyword.clone(),
+            });
+        } else if let Some(label) = &label {
+            let found = self
+                .loops
+                .iter()
+                .flatten()
+                .any(|outer| outer.name() == label.name());
+            if !found {
+                self.errors.push(ParseErr {
+                    code: "parse-unknown-label",
+                    message: format!("No enclosing loop is labeled '{}'.", label.name()),
+                    token: label.clone(),
+                });
+            }
+        }
+
+        consume!(
+            self,
+            TokenKind::Semicolon,
+            "parse-expect-semicolon",
+            "Expect ';' after loop control statement."
+        )?;
+        Ok(match keyword.kind {
+            TokenKind::Break => Stmt::Break { keyword, label },
+            _ => Stmt::Continue { keyword, label },
         })
     }
 
@@ -219,13 +557,23 @@ impl Parser {
         while !check!(self, TokenKind::RightBrace) && !self.is_at_end() {
             statements.push(self.declaration()?);
         }
-        consume!(self, TokenKind::RightBrace, "Expect '}' after block.")?;
+        consume!(
+            self,
+            TokenKind::RightBrace,
+            "parse-expect-right-brace",
+            "Expect '}' after block."
+        )?;
         Ok(statements)
     }
 
     fn expression_statement(&mut self) -> Result<Stmt, ParseErr> {
         let expr = self.expression()?;
-        consume!(self, TokenKind::Semicolon, "Expect ';' after value.")?;
+        consume!(
+            self,
+            TokenKind::Semicolon,
+            "parse-expect-semicolon",
+            "Expect ';' after value."
+        )?;
         Ok(Stmt::Expression { expr })
     }
 
@@ -238,14 +586,38 @@ impl Parser {
         if let Some(equals) = eat!(self, TokenKind::Equal) {
             let value = self.assignment()?;
 
-            if let Expr::Variable { name } = expr {
-                return Ok(Expr::Assign {
-                    name,
-                    value: Box::new(value),
-                });
+            match expr {
+                Expr::Variable { name, .. } => {
+                    return Ok(Expr::Assign {
+                        name,
+                        value: Box::new(value),
+                        depth: Depth::default(),
+                    })
+                }
+                Expr::Get { object, name } => {
+                    return Ok(Expr::Set {
+                        object,
+                        name,
+                        value: Box::new(value),
+                    })
+                }
+                Expr::Index {
+                    object,
+                    bracket,
+                    index,
+                } => {
+                    return Ok(Expr::IndexSet {
+                        object,
+                        bracket,
+                        index,
+                        value: Box::new(value),
+                    })
+                }
+                _ => {}
             }
 
             return Err(ParseErr {
+                code: "parse-invalid-assignment-target",
                 token: equals,
                 message: "Invalid assignment target.".to_string(),
             });
@@ -294,10 +666,10 @@ impl Parser {
     }
 
     fn comparison(&mut self) -> Result<Expr, ParseErr> {
-        let mut expr = self.addition()?;
+        let mut expr = self.range()?;
         use TokenKind::*;
         while let Some(operator) = eat!(self, Greater, GreaterEqual, Less, LessEqual) {
-            let right = self.addition()?;
+            let right = self.range()?;
             expr = Expr::Binary {
                 left: Box::new(expr),
                 operator,
@@ -307,6 +679,19 @@ impl Parser {
         Ok(expr)
     }
 
+    /// `start..end` or `start..=end`. ranges don't chain, so there's no loop
+    fn range(&mut self) -> Result<Expr, ParseErr> {
+        let expr = self.addition()?;
+        match eat!(self, TokenKind::DotDot, TokenKind::DotDotEqual) {
+            Some(operator) => Ok(Expr::Binary {
+                left: Box::new(expr),
+                operator,
+                right: Box::new(self.addition()?),
+            }),
+            None => Ok(expr),
+        }
+    }
+
     fn addition(&mut self) -> Result<Expr, ParseErr> {
         let mut expr = self.multiplication()?;
         while let Some(operator) = eat!(self, TokenKind::Minus, TokenKind::Plus) {
@@ -322,7 +707,8 @@ impl Parser {
 
     fn multiplication(&mut self) -> Result<Expr, ParseErr> {
         let mut expr = self.unary()?;
-        while let Some(operator) = eat!(self, TokenKind::Slash, TokenKind::Star) {
+        use TokenKind::*;
+        while let Some(operator) = eat!(self, Slash, Star, Percent, TildeSlash) {
             let right = self.unary()?;
             expr = Expr::Binary {
                 left: Box::new(expr),
@@ -341,8 +727,110 @@ impl Parser {
                 right: Box::new(right),
             })
         } else {
-            self.primary()
+            self.power()
+        }
+    }
+
+    /// `**` binds tighter than unary operators on its left, so `-2 ** 2` is `-(2 ** 2)`, and is
+    /// right associative, so `2 ** 3 ** 2` is `2 ** (3 ** 2)`
+    fn power(&mut self) -> Result<Expr, ParseErr> {
+        let expr = self.call()?;
+        if let Some(operator) = eat!(self, TokenKind::StarStar) {
+            let right = self.unary()?;
+            return Ok(Expr::Binary {
+                left: Box::new(expr),
+                operator,
+                right: Box::new(right),
+            });
         }
+        Ok(expr)
+    }
+
+    fn call(&mut self) -> Result<Expr, ParseErr> {
+        let mut expr = self.primary()?;
+        loop {
+            if did_eat!(self, TokenKind::LeftParen) {
+                expr = self.finish_call(expr)?;
+            } else if did_eat!(self, TokenKind::Dot) {
+                let name = consume!(
+                    self,
+                    TokenKind::Identifier,
+                    "parse-expect-name",
+                    "Expect property name after '.'."
+                )?;
+                expr = Expr::Get {
+                    object: Box::new(expr),
+                    name,
+                };
+            } else if let Some(bracket) = eat!(self, TokenKind::LeftBracket) {
+                expr = self.finish_index(expr, bracket)?;
+            } else {
+                break;
+            }
+        }
+        Ok(expr)
+    }
+
+    fn finish_call(&mut self, callee: Expr) -> Result<Expr, ParseErr> {
+        let mut arguments = Vec::new();
+        if !check!(self, TokenKind::RightParen) {
+            loop {
+                arguments.push(self.expression()?);
+                if !did_eat!(self, TokenKind::Comma) {
+                    break;
+                }
+            }
+        }
+        let paren = consume!(
+            self,
+            TokenKind::RightParen,
+            "parse-expect-right-paren",
+            "Expect ')' after arguments."
+        )?;
+
+        Ok(Expr::Call {
+            callee: Box::new(callee),
+            paren,
+            arguments,
+        })
+    }
+
+    /// an index or a slice, after the `[`
+    fn finish_index(&mut self, object: Expr, bracket: Token) -> Result<Expr, ParseErr> {
+        let start = if check!(self, TokenKind::Colon) {
+            None
+        } else {
+            Some(Box::new(self.expression()?))
+        };
+
+        let expr = match start {
+            Some(index) if !check!(self, TokenKind::Colon) => Expr::Index {
+                object: Box::new(object),
+                bracket,
+                index,
+            },
+            start => {
+                self.advance();
+                let end = if check!(self, TokenKind::RightBracket) {
+                    None
+                } else {
+                    Some(Box::new(self.expression()?))
+                };
+                Expr::Slice {
+                    object: Box::new(object),
+                    bracket,
+                    start,
+                    end,
+                }
+            }
+        };
+        consume!(
+            self,
+            TokenKind::RightBracket,
+            "parse-expect-right-bracket",
+            "Expect ']' after index."
+        )?;
+        Ok(expr)
     }
 
     fn primary(&mut self) -> Result<Expr, ParseErr> {
@@ -359,24 +847,134 @@ impl Parser {
             Number(val) => Ok(Expr::Literal {
                 val: Literal::Number(val),
             }),
+            Integer(val) => Ok(Expr::Literal {
+                val: Literal::Integer(val),
+            }),
+            BigInt(val) => Ok(Expr::Literal {
+                val: Literal::BigInt(val),
+            }),
             Str(contents) => Ok(Expr::Literal {
                 val: Literal::Str(contents),
             }),
+            Interpolation(contents) => self.interpolation(contents),
+            LeftBracket => {
+                let mut elements = Vec::new();
+                while !check!(self, TokenKind::RightBracket) {
+                    elements.push(self.expression()?);
+                    if !did_eat!(self, TokenKind::Comma) {
+                        break;
+                    }
+                }
+                consume!(
+                    self,
+                    TokenKind::RightBracket,
+                    "parse-expect-right-bracket",
+                    "Expect ']' after list elements."
+                )?;
+                Ok(Expr::List { elements })
+            }
+            LeftBrace => {
+                let mut entries = Vec::new();
+                while !check!(self, TokenKind::RightBrace) {
+                    let key = self.expression()?;
+                    consume!(
+                        self,
+                        TokenKind::Colon,
+                        "parse-expect-colon",
+                        "Expect ':' after map key."
+                    )?;
+                    entries.push((key, self.expression()?));
+                    if !did_eat!(self, TokenKind::Comma) {
+                        break;
+                    }
+                }
+                consume!(
+                    self,
+                    TokenKind::RightBrace,
+                    "parse-expect-right-brace",
+                    "Expect '}' after map entries."
+                )?;
+                Ok(Expr::Map {
+                    brace: tok,
+                    entries,
+                })
+            }
             LeftParen => {
                 let expr = self.expression()?;
-                consume!(self, TokenKind::RightParen, "Expect ')' after expression.")?;
+                consume!(
+                    self,
+                    TokenKind::RightParen,
+                    "parse-expect-right-paren",
+                    "Expect ')' after expression."
+                )?;
                 Ok(Expr::Grouping {
                     expr: Box::new(expr),
                 })
             }
-            Identifier => Ok(Expr::Variable { name: tok }),
+            This => Ok(Expr::This {
+                keyword: tok,
+                depth: Depth::default(),
+            }),
+            Super => {
+                consume!(
+                    self,
+                    TokenKind::Dot,
+                    "parse-expect-dot",
+                    "Expect '.' after 'super'."
+                )?;
+                let method = consume!(
+                    self,
+                    TokenKind::Identifier,
+                    "parse-expect-name",
+                    "Expect superclass method name."
+                )?;
+                Ok(Expr::Super {
+                    keyword: tok,
+                    method,
+                    depth: Depth::default(),
+                })
+            }
+            Identifier => Ok(Expr::Variable {
+                name: tok,
+                depth: Depth::default(),
+            }),
             _ => Err(ParseErr {
+                code: "parse-expect-expression",
                 token: tok,
                 message: "Expect expression.".to_string(),
             }),
         }
     }
 
+    /// the rest of an interpolated string, after the text before its first expression
+    fn interpolation(&mut self, head: String) -> Result<Expr, ParseErr> {
+        let mut parts = vec![Expr::Literal {
+            val: Literal::Str(head),
+        }];
+        loop {
+            parts.push(self.expression()?);
+            let tok = self.advance();
+            match tok.kind {
+                TokenKind::Interpolation(contents) => parts.push(Expr::Literal {
+                    val: Literal::Str(contents),
+                }),
+                TokenKind::Str(contents) => {
+                    parts.push(Expr::Literal {
+                        val: Literal::Str(contents),
+                    });
+                    return Ok(Expr::Interpolation { parts });
+                }
+                _ => {
+                    return Err(ParseErr {
+                        code: "parse-expect-right-brace",
+                        token: tok,
+                        message: "Expect '}' after interpolated expression.".to_string(),
+                    })
+                }
+            }
+        }
+    }
+
     fn synchronize(&mut self) {
         self.advance();
         while !self.is_at_end() {
@@ -408,15 +1006,13 @@ impl Parser {
     }
 
     fn is_at_end(&self) -> bool {
-        if let Token {
-            kind: TokenKind::Eof,
-            ..
-        } = self.peek()
-        {
-            true
-        } else {
-            false
-        }
+        matches!(
+            self.peek(),
+            Token {
+                kind: TokenKind::Eof,
+                ..
+            }
+        )
     }
 
     fn peek(&self) -> Token {
@@ -443,6 +1039,7 @@ mod test {
         move |kind| Token {
             kind,
             line: 1,
+            column: 1,
             span: Range(0, 0),
             source: Rc::clone(&source),
         }
@@ -451,7 +1048,7 @@ mod test {
     #[test]
     fn test_eof() {
         let token = new_token_factory();
-        assert_eq!(parse(vec![token(Eof)]), vec![],);
+        assert_eq!(parse(vec![token(Eof)]).0, vec![],);
     }
 
     #[test]
@@ -465,13 +1062,153 @@ mod test {
                 token(Number(0f64)),
                 token(Semicolon),
                 token(Eof),
-            ]),
+            ])
+            .0,
             vec![Stmt::Var {
                 name: token(Identifier),
                 initializer: Some(Expr::Literal {
                     val: crate::token::Literal::Number(0.0f64)
                 }),
+                doc: None,
+            }],
+        );
+    }
+
+    #[test]
+    fn test_call() {
+        let token = new_token_factory();
+        assert_eq!(
+            parse(vec![
+                token(Identifier),
+                token(LeftParen),
+                token(Number(1f64)),
+                token(Comma),
+                token(Number(2f64)),
+                token(RightParen),
+                token(Semicolon),
+                token(Eof),
+            ])
+            .0,
+            vec![Stmt::Expression {
+                expr: Expr::Call {
+                    callee: Box::new(Expr::Variable {
+                        name: token(Identifier),
+                        depth: Depth::default(),
+                    }),
+                    paren: token(RightParen),
+                    arguments: vec![
+                        Expr::Literal {
+                            val: crate::token::Literal::Number(1.0f64)
+                        },
+                        Expr::Literal {
+                            val: crate::token::Literal::Number(2.0f64)
+                        },
+                    ],
+                },
+            }],
+        );
+    }
+
+    #[test]
+    fn test_set_property() {
+        let token = new_token_factory();
+        assert_eq!(
+            parse(vec![
+                token(This),
+                token(Dot),
+                token(Identifier),
+                token(Equal),
+                token(Number(1f64)),
+                token(Semicolon),
+                token(Eof),
+            ])
+            .0,
+            vec![Stmt::Expression {
+                expr: Expr::Set {
+                    object: Box::new(Expr::This {
+                        keyword: token(This),
+                        depth: Depth::default(),
+                    }),
+                    name: token(Identifier),
+                    value: Box::new(Expr::Literal {
+                        val: crate::token::Literal::Number(1.0f64)
+                    }),
+                },
             }],
         );
     }
+
+    #[test]
+    fn test_doc_comments() {
+        let source = "
+            /// a counter
+            /// that counts
+            class Counter {
+                /// go up by one
+                inc() {}
+                dec() {}
+            }
+            /// not attached to anything
+            print 1;
+            /// the answer
+            var answer = 42;
+            fun f() {}
+        ";
+        let statements = parse(crate::lex::lex("<for testing>".to_string(), source).0).0;
+        let docs: Vec<Option<&str>> = statements
+            .iter()
+            .flat_map(|statement| match statement {
+                Stmt::Class { methods, doc, .. } => std::iter::once(doc.as_deref())
+                    .chain(methods.iter().map(|method| method.doc.as_deref()))
+                    .collect(),
+                Stmt::Var { doc, .. } => vec![doc.as_deref()],
+                Stmt::Function { decl } => vec![decl.doc.as_deref()],
+                _ => vec![],
+            })
+            .collect();
+        assert_eq!(
+            docs,
+            vec![
+                Some("a counter\nthat counts"),
+                Some("go up by one"),
+                None,
+                Some("the answer"),
+                None,
+            ],
+        );
+    }
+
+    #[test]
+    fn test_inherit_from_itself() {
+        let (_, errors) = parse(crate::lex::lex("<for testing>".to_string(), "class A < A {}").0);
+        assert_eq!(errors.len(), 1);
+        assert_eq!(errors[0].message, "A class can't inherit from itself.");
+        assert_eq!(errors[0].token.span, Range(10, 11));
+    }
+
+    #[test]
+    fn test_loop_control_errors() {
+        let errors = |source: &str| -> Vec<String> {
+            parse(crate::lex::lex("<for testing>".to_string(), source).0)
+                .1
+                .into_iter()
+                .map(|error| error.message)
+                .collect()
+        };
+        assert_eq!(
+            errors("break; while (true) { fun f() { continue; } break; }"),
+            vec![
+                "Can't use 'break' outside of a loop.",
+                "Can't use 'continue' outside of a loop.",
+            ],
+        );
+        assert_eq!(
+            errors("a: while (true) { b: while (true) { a: for (;;) break c; continue b; } } x: print 1;"),
+            vec![
+                "Label 'a' is already used by an enclosing loop.",
+                "No enclosing loop is labeled 'c'.",
+                "Expect loop after label.",
+            ],
+        );
+    }
 }
@@ -0,0 +1,457 @@
+use crate::stmt::{Depth, Expr, FunctionDecl, Stmt};
+use crate::token::Token;
+use std::collections::HashMap;
+
+/// work out which scope every local variable reference points at, and report scoping mistakes
+/// that can be caught before the program runs
+pub fn resolve(statements: &[Stmt]) -> Result<(), Vec<ResolveErr>> {
+    let mut resolver = Resolver {
+        scopes: Vec::new(),
+        function: FunctionKind::None,
+        class: ClassKind::None,
+        errors: Vec::new(),
+    };
+    resolver.resolve_statements(statements);
+
+    if resolver.errors.is_empty() {
+        Ok(())
+    } else {
+        Err(resolver.errors)
+    }
+}
+
+#[derive(Debug)]
+pub struct ResolveErr {
+    /// stable identifier for the kind of error, see `Diagnostic::code`
+    pub code: &'static str,
+    pub token: Token,
+    pub message: String,
+    /// for redeclarations, where the variable was first declared
+    pub previous: Option<Token>,
+}
+
+/// a local variable as seen by the resolver
+struct Binding {
+    /// whether the variable's initializer has finished running
+    defined: bool,
+    /// `None` for implicit variables like `this`
+    declaration: Option<Token>,
+}
+
+#[derive(Clone, Copy, PartialEq)]
+enum FunctionKind {
+    None,
+    Function,
+    Initializer,
+    Method,
+}
+
+#[derive(Clone, Copy, PartialEq)]
+enum ClassKind {
+    None,
+    Class,
+    Subclass,
+}
+
+struct Resolver {
+    /// local scopes only; globals are looked up dynamically
+    scopes: Vec<HashMap<String, Binding>>,
+    function: FunctionKind,
+    class: ClassKind,
+    errors: Vec<ResolveErr>,
+}
+
+impl Resolver {
+    fn resolve_statements(&mut self, statements: &[Stmt]) {
+        for statement in statements {
+            self.resolve_statement(statement);
+        }
+    }
+
+    fn resolve_statement(&mut self, statement: &Stmt) {
+        match statement {
+            Stmt::Expression { expr } | Stmt::Print { expr } => self.resolve_expression(expr),
+            Stmt::Var {
+                name, initializer, ..
+            } => {
+                self.declare(name);
+                if let Some(initializer) = initializer {
+                    self.resolve_expression(initializer);
+                }
+                self.define(name);
+            }
+            Stmt::Block { statements } => {
+                self.scopes.push(HashMap::new());
+                self.resolve_statements(statements);
+                self.scopes.pop();
+            }
+            Stmt::If {
+                condition,
+                then_branch,
+                else_branch,
+            } => {
+                self.resolve_expression(condition);
+                self.resolve_statement(then_branch);
+                if let Some(else_branch) = else_branch {
+                    self.resolve_statement(else_branch);
+                }
+            }
+            Stmt::While {
+                condition,
+                body,
+                increment,
+                ..
+            } => {
+                self.resolve_expression(condition);
+                self.resolve_statement(body);
+                if let Some(increment) = increment {
+                    self.resolve_expression(increment);
+                }
+            }
+            Stmt::ForIn {
+                name,
+                iterable,
+                body,
+                ..
+            } => {
+                self.resolve_expression(iterable);
+                // every iteration gets a new scope holding the loop variable
+                self.scopes.push(HashMap::new());
+                self.declare(name);
+                self.define(name);
+                self.resolve_statement(body);
+                self.scopes.pop();
+            }
+            Stmt::Break { .. } | Stmt::Continue { .. } => {}
+            Stmt::Function { decl } => {
+                // define eagerly so the function can refer to itself recursively
+                self.declare(&decl.name);
+                self.define(&decl.name);
+                self.resolve_function(decl, FunctionKind::Function);
+            }
+            Stmt::Return { keyword, value } => {
+                if self.function == FunctionKind::None {
+                    self.error(
+                        "resolve-return-outside-function",
+                        keyword,
+                        "Can't return from top-level code.",
+                    );
+                }
+                if let Some(value) = value {
+                    if self.function == FunctionKind::Initializer {
+                        self.error(
+                            "resolve-return-from-initializer",
+                            keyword,
+                            "Can't return a value from an initializer.",
+                        );
+                    }
+                    self.resolve_expression(value);
+                }
+            }
+            Stmt::Class {
+                name,
+                superclass,
+                methods,
+                ..
+            } => {
+                let enclosing_class = self.class;
+                self.class = ClassKind::Class;
+
+                self.declare(name);
+                self.define(name);
+
+                if let Some(superclass) = superclass {
+                    self.class = ClassKind::Subclass;
+                    self.resolve_expression(superclass);
+                    self.begin_scope_with("super");
+                }
+
+                self.begin_scope_with("this");
+                for method in methods {
+                    let kind = if method.name.name() == "init" {
+                        FunctionKind::Initializer
+                    } else {
+                        FunctionKind::Method
+                    };
+                    self.resolve_function(method, kind);
+                }
+                self.scopes.pop();
+
+                if superclass.is_some() {
+                    self.scopes.pop();
+                }
+
+                self.class = enclosing_class;
+            }
+        }
+    }
+
+    fn resolve_function(&mut self, decl: &FunctionDecl, kind: FunctionKind) {
+        let enclosing_function = self.function;
+        self.function = kind;
+
+        self.scopes.push(HashMap::new());
+        for param in &decl.params {
+            self.declare(param);
+            self.define(param);
+        }
+        self.resolve_statements(&decl.body);
+        self.scopes.pop();
+
+        self.function = enclosing_function;
+    }
+
+    fn resolve_expression(&mut self, expr: &Expr) {
+        match expr {
+            Expr::Literal { .. } => {}
+            Expr::Grouping { expr } => self.resolve_expression(expr),
+            Expr::Unary { right, .. } => self.resolve_expression(right),
+            Expr::Binary { left, right, .. } | Expr::Logical { left, right, .. } => {
+                self.resolve_expression(left);
+                self.resolve_expression(right);
+            }
+            Expr::Variable { name, depth } => {
+                let in_own_initializer = self
+                    .scopes
+                    .last()
+                    .and_then(|scope| scope.get(&name.name()))
+                    .is_some_and(|binding| !binding.defined);
+                if in_own_initializer {
+                    self.error(
+                        "resolve-self-referencing-initializer",
+                        name,
+                        "Can't read local variable in its own initializer.",
+                    );
+                }
+                self.resolve_local(name, depth);
+            }
+            Expr::Assign { name, value, depth } => {
+                self.resolve_expression(value);
+                self.resolve_local(name, depth);
+            }
+            Expr::Interpolation { parts: exprs } | Expr::List { elements: exprs } => {
+                for expr in exprs {
+                    self.resolve_expression(expr);
+                }
+            }
+            Expr::Map { entries, .. } => {
+                for (key, value) in entries {
+                    self.resolve_expression(key);
+                    self.resolve_expression(value);
+                }
+            }
+            Expr::Index { object, index, .. } => {
+                self.resolve_expression(object);
+                self.resolve_expression(index);
+            }
+            Expr::IndexSet {
+                object,
+                index,
+                value,
+                ..
+            } => {
+                self.resolve_expression(object);
+                self.resolve_expression(index);
+                self.resolve_expression(value);
+            }
+            Expr::Slice {
+                object, start, end, ..
+            } => {
+                self.resolve_expression(object);
+                for bound in start.iter().chain(end) {
+                    self.resolve_expression(bound);
+                }
+            }
+            Expr::Call {
+                callee, arguments, ..
+            } => {
+                self.resolve_expression(callee);
+                for argument in arguments {
+                    self.resolve_expression(argument);
+                }
+            }
+            Expr::Get { object, .. } => self.resolve_expression(object),
+            Expr::Set { object, value, .. } => {
+                self.resolve_expression(value);
+                self.resolve_expression(object);
+            }
+            Expr::This { keyword, depth } => {
+                if self.class == ClassKind::None {
+                    self.error(
+                        "resolve-this-outside-class",
+                        keyword,
+                        "Can't use 'this' outside of a class.",
+                    );
+                    return;
+                }
+                self.resolve_local(keyword, depth);
+            }
+            Expr::Super { keyword, depth, .. } => {
+                match self.class {
+                    ClassKind::None => self.error(
+                        "resolve-super-outside-class",
+                        keyword,
+                        "Can't use 'super' outside of a class.",
+                    ),
+                    ClassKind::Class => self.error(
+                        "resolve-super-without-superclass",
+                        keyword,
+                        "Can't use 'super' in a class with no superclass.",
+                    ),
+                    ClassKind::Subclass => {}
+                }
+                self.resolve_local(keyword, depth);
+            }
+        }
+    }
+
+    fn resolve_local(&mut self, name: &Token, depth: &Depth) {
+        let name = name.name();
+        let found = self
+            .scopes
+            .iter()
+            .rev()
+            .position(|scope| scope.contains_key(&name));
+        depth.set(found);
+    }
+
+    /// push a scope holding a single implicitly defined variable, e.g. `this`
+    fn begin_scope_with(&mut self, name: &str) {
+        let mut scope = HashMap::new();
+        scope.insert(
+            name.to_string(),
+            Binding {
+                defined: true,
+                declaration: None,
+            },
+        );
+        self.scopes.push(scope);
+    }
+
+    fn declare(&mut self, name: &Token) {
+        let scope = match self.scopes.last_mut() {
+            Some(scope) => scope,
+            None => return,
+        };
+        let binding = Binding {
+            defined: false,
+            declaration: Some(name.clone()),
+        };
+        if let Some(previous) = scope.insert(name.name(), binding) {
+            self.errors.push(ResolveErr {
+                code: "resolve-redeclared-variable",
+                token: name.clone(),
+                message: "Already a variable with this name in this scope.".to_string(),
+                previous: previous.declaration,
+            });
+        }
+    }
+
+    fn define(&mut self, name: &Token) {
+        if let Some(binding) = self
+            .scopes
+            .last_mut()
+            .and_then(|scope| scope.get_mut(&name.name()))
+        {
+            binding.defined = true;
+        }
+    }
+
+    fn error(&mut self, code: &'static str, token: &Token, message: &str) {
+        self.errors.push(ResolveErr {
+            code,
+            token: token.clone(),
+            message: message.to_string(),
+            previous: None,
+        });
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{lex::lex, parse::parse};
+
+    fn resolve_errors(source: &str) -> Vec<String> {
+        let statements = parse(lex("<for testing>".to_string(), source).0).0;
+        match resolve(&statements) {
+            Ok(()) => vec![],
+            Err(errors) => errors.into_iter().map(|error| error.message).collect(),
+        }
+    }
+
+    #[test]
+    fn test_valid_program() {
+        assert_eq!(
+            resolve_errors("var a = 1; { var b = a; } fun f(x) { return x; }"),
+            Vec::<String>::new(),
+        );
+    }
+
+    #[test]
+    fn test_own_initializer() {
+        assert_eq!(
+            resolve_errors("{ var a = a; }"),
+            vec!["Can't read local variable in its own initializer."],
+        );
+    }
+
+    #[test]
+    fn test_redeclaration() {
+        assert_eq!(
+            resolve_errors("fun f() { var a = 1; var a = 2; }"),
+            vec!["Already a variable with this name in this scope."],
+        );
+    }
+
+    #[test]
+    fn test_top_level_return() {
+        assert_eq!(
+            resolve_errors("return 1;"),
+            vec!["Can't return from top-level code."],
+        );
+    }
+
+    #[test]
+    fn test_this_and_super_outside_class() {
+        assert_eq!(
+            resolve_errors("print this; fun f() { super.g(); }"),
+            vec![
+                "Can't use 'this' outside of a class.",
+                "Can't use 'super' outside of a class.",
+            ],
+        );
+    }
+
+    #[test]
+    fn test_super_without_superclass() {
+        assert_eq!(
+            resolve_errors("class A { f() { super.f(); } }"),
+            vec!["Can't use 'super' in a class with no superclass."],
+        );
+    }
+
+    #[test]
+    fn test_depth() {
+        let statements = parse(lex("<for testing>".to_string(), "var a; { var b; { a; b; } }").0).0;
+        resolve(&statements).unwrap();
+
+        let inner = match &statements[1] {
+            Stmt::Block { statements } => match &statements[1] {
+                Stmt::Block { statements } => statements,
+                _ => unreachable!(),
+            },
+            _ => unreachable!(),
+        };
+        let depths: Vec<_> = inner
+            .iter()
+            .map(|statement| match statement {
+                Stmt::Expression {
+                    expr: Expr::Variable { depth, .. },
+                } => depth.get(),
+                _ => unreachable!(),
+            })
+            .collect();
+        assert_eq!(depths, vec![None, Some(1)]);
+    }
+}
@@ -1,4 +1,10 @@
 use crate::token::{Literal, Token};
+use std::cell::Cell;
+use std::rc::Rc;
+
+/// how many scopes out from its use a local variable was declared. filled in by the resolver;
+/// `None` means the variable is global
+pub type Depth = Cell<Option<usize>>;
 
 #[derive(Debug, PartialEq)]
 pub enum Expr {
@@ -19,16 +25,74 @@ pub enum Expr {
     },
     Variable {
         name: Token,
+        depth: Depth,
     },
     Assign {
         name: Token,
         value: Box<Expr>,
+        depth: Depth,
     },
     Logical {
         left: Box<Expr>,
         operator: Token,
         right: Box<Expr>,
     },
+    Call {
+        callee: Box<Expr>,
+        paren: Token,
+        arguments: Vec<Expr>,
+    },
+    Get {
+        object: Box<Expr>,
+        name: Token,
+    },
+    Set {
+        object: Box<Expr>,
+        name: Token,
+        value: Box<Expr>,
+    },
+    This {
+        keyword: Token,
+        depth: Depth,
+    },
+    Super {
+        keyword: Token,
+        method: Token,
+        depth: Depth,
+    },
+    List {
+        elements: Vec<Expr>,
+    },
+    /// `{key: value, ...}`
+    Map {
+        brace: Token,
+        entries: Vec<(Expr, Expr)>,
+    },
+    /// `object[index]`
+    Index {
+        object: Box<Expr>,
+        bracket: Token,
+        index: Box<Expr>,
+    },
+    /// `object[index] = value`
+    IndexSet {
+        object: Box<Expr>,
+        bracket: Token,
+        index: Box<Expr>,
+        value: Box<Expr>,
+    },
+    /// `object[start:end]`, where either bound can be left out
+    Slice {
+        object: Box<Expr>,
+        bracket: Token,
+        start: Option<Box<Expr>>,
+        end: Option<Box<Expr>>,
+    },
+    /// a string with expressions embedded in it, e.g. `"x=${x}"`. the parts alternate between
+    /// string literals and expressions, and are stringified and joined
+    Interpolation {
+        parts: Vec<Expr>,
+    },
 }
 
 #[derive(Debug, PartialEq)]
@@ -42,6 +106,7 @@ pub enum Stmt {
     Var {
         name: Token,
         initializer: Option<Expr>,
+        doc: Option<String>,
     },
     Block {
         statements: Vec<Stmt>,
@@ -54,5 +119,50 @@ pub enum Stmt {
     While {
         condition: Expr,
         body: Box<Stmt>,
+        /// run after the body on every iteration, even one cut short by `continue`. only set
+        /// for desugared `for` loops
+        increment: Option<Expr>,
+        label: Option<Token>,
+    },
+    /// `for (var name in iterable) body`, where `keyword` is the `in`
+    ForIn {
+        name: Token,
+        keyword: Token,
+        iterable: Expr,
+        body: Box<Stmt>,
+        label: Option<Token>,
+    },
+    /// `label` is `None` for the innermost loop
+    Break {
+        keyword: Token,
+        label: Option<Token>,
+    },
+    Continue {
+        keyword: Token,
+        label: Option<Token>,
     },
+    Function {
+        decl: Rc<FunctionDecl>,
+    },
+    Return {
+        keyword: Token,
+        value: Option<Expr>,
+    },
+    Class {
+        name: Token,
+        /// always an `Expr::Variable`
+        superclass: Option<Expr>,
+        methods: Vec<Rc<FunctionDecl>>,
+        doc: Option<String>,
+    },
+}
+
+/// a `fun` declaration. shared between the AST and every function value created from it
+#[derive(Debug, PartialEq)]
+pub struct FunctionDecl {
+    pub name: Token,
+    pub params: Vec<Token>,
+    pub body: Vec<Stmt>,
+    /// text of the `///` comments just before the declaration, one line per comment
+    pub doc: Option<String>,
 }
@@ -1,3 +1,10 @@
+use crate::bigint::BigInt;
+use crate::class::{LoxClass, LoxInstance};
+use crate::function::LoxFunction;
+use crate::map::LoxMap;
+use crate::native::NativeFunction;
+use std::cell::RefCell;
+use std::cmp::Ordering;
 use std::fmt::Debug;
 use std::rc::Rc;
 
@@ -27,6 +34,32 @@ impl Source {
     pub fn len(&self) -> usize {
         self.chars.len()
     }
+
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    /// 1-based line and column (in chars) of the char at `index`
+    pub fn position(&self, index: usize) -> (usize, usize) {
+        let index = index.min(self.chars.len());
+        let line = 1 + self.chars[..index].iter().filter(|&&c| c == '\n').count();
+        let column = 1 + index - self.line_bounds(index).0;
+        (line, column)
+    }
+
+    /// the range covering the line that the char at `index` is on, without its newline
+    pub fn line_bounds(&self, index: usize) -> Range {
+        let index = index.min(self.chars.len());
+        let start = self.chars[..index]
+            .iter()
+            .rposition(|&c| c == '\n')
+            .map_or(0, |newline| newline + 1);
+        let end = self.chars[index..]
+            .iter()
+            .position(|&c| c == '\n')
+            .map_or(self.chars.len(), |newline| index + newline);
+        Range(start, end)
+    }
 }
 
 #[derive(Clone, Debug, Eq, Hash, PartialEq)]
@@ -37,6 +70,8 @@ pub struct Token {
     pub kind: TokenKind,
     pub span: Range,
     pub line: usize,
+    /// 1-based, counted in chars
+    pub column: usize,
     pub source: Rc<Source>,
 }
 
@@ -62,13 +97,17 @@ pub enum TokenKind {
     RightParen,
     LeftBrace,
     RightBrace,
+    LeftBracket,
+    RightBracket,
     Comma,
     Dot,
     Minus,
     Plus,
     Semicolon,
+    Colon,
     Slash,
     Star,
+    Percent,
 
     // OneOrTwoCharacterTokens
     Bang,
@@ -79,20 +118,36 @@ pub enum TokenKind {
     GreaterEqual,
     Less,
     LessEqual,
+    StarStar,
+    TildeSlash,
+    DotDot,
+    DotDotEqual,
 
     // Literals
     Identifier,
     Str(String),
+    /// the part of a string before an interpolated expression, e.g. `"a ${`. the expression's
+    /// tokens come next, followed by either another `Interpolation` or the rest of the string
+    /// as a `Str`
+    Interpolation(String),
+    /// a `///` comment, without the slashes
+    DocComment(String),
     Number(f64),
+    Integer(i64),
+    /// an integer literal too large for an `i64`
+    BigInt(BigInt),
 
     // Keywords
     And,
+    Break,
     Class,
+    Continue,
     Else,
     False,
     Fun,
     For,
     If,
+    In,
     Nil,
     Or,
     Print,
@@ -105,12 +160,29 @@ pub enum TokenKind {
     Eof,
 }
 
-#[derive(Clone, Debug, PartialEq)]
+#[derive(Clone, Debug)]
 pub enum Literal {
     Bool(bool),
     Nil,
     Number(f64),
+    Integer(i64),
+    /// only used for integers that don't fit in an `i64`, so each integer has one representation
+    BigInt(BigInt),
     Str(String),
+    Function(Rc<LoxFunction>),
+    Native(Rc<NativeFunction>),
+    Class(Rc<LoxClass>),
+    Instance(Rc<RefCell<LoxInstance>>),
+    /// lists are shared, not copied, when assigned or passed around
+    List(Rc<RefCell<Vec<Literal>>>),
+    /// maps are shared like lists
+    Map(Rc<RefCell<LoxMap>>),
+    /// `start..end`, or `start..=end` if it's inclusive
+    Range {
+        start: i64,
+        end: i64,
+        inclusive: bool,
+    },
 }
 
 impl Literal {
@@ -121,24 +193,277 @@ impl Literal {
         }
     }
 
+    /// the value of a number as a float, which may lose precision for large integers
+    pub fn as_float(&self) -> Option<f64> {
+        match self {
+            Literal::Number(num) => Some(*num),
+            Literal::Integer(num) => Some(*num as f64),
+            Literal::BigInt(num) => Some(num.to_f64()),
+            _ => None,
+        }
+    }
+
+    /// compare two numbers exactly, so the ordering agrees with `==` even for integers that no
+    /// float can represent. `None` if either isn't a number, or is nan
+    pub fn compare_number(&self, other: &Literal) -> Option<Ordering> {
+        match (self, other) {
+            (Literal::Integer(left), Literal::Integer(right)) => Some(left.cmp(right)),
+            (Literal::Number(left), Literal::Number(right)) => left.partial_cmp(right),
+            (Literal::Number(_), _) => other.compare_number(self).map(Ordering::reverse),
+            (_, Literal::Number(float)) => {
+                let int = self.as_big()?;
+                if float.is_nan() {
+                    None
+                } else if float.is_infinite() {
+                    Some(if *float > 0.0 {
+                        Ordering::Less
+                    } else {
+                        Ordering::Greater
+                    })
+                } else {
+                    // the floor of a finite float is exact, and any fraction breaks a tie
+                    let floor = BigInt::from_f64(float.floor()).expect("the floor is integral");
+                    let fraction = if float.fract() == 0.0 {
+                        Ordering::Equal
+                    } else {
+                        Ordering::Less
+                    };
+                    Some(int.cmp(&floor).then(fraction))
+                }
+            }
+            _ => Some(self.as_big()?.cmp(&other.as_big()?)),
+        }
+    }
+
+    fn as_big(&self) -> Option<BigInt> {
+        match self {
+            Literal::Integer(num) => Some(BigInt::from(*num)),
+            Literal::BigInt(num) => Some(num.clone()),
+            _ => None,
+        }
+    }
+
     pub fn kind_name(&self) -> &str {
         match self {
             Literal::Bool(_) => "bool",
             Literal::Nil => "nil",
-            Literal::Number(_) => "number",
+            Literal::Number(_) | Literal::Integer(_) | Literal::BigInt(_) => "number",
             Literal::Str(_) => "string",
+            Literal::Function(_) | Literal::Native(_) => "function",
+            Literal::Class(_) => "class",
+            Literal::Instance(_) => "instance",
+            Literal::List(_) => "list",
+            Literal::Map(_) => "map",
+            Literal::Range { .. } => "range",
+        }
+    }
+
+    /// `Display`, but keeping track of the lists and maps being printed so that one containing
+    /// itself is shown as `[...]` or `{...}` rather than recursing forever
+    fn fmt_nested(
+        &self,
+        f: &mut std::fmt::Formatter<'_>,
+        outer: &mut Vec<*const ()>,
+    ) -> std::fmt::Result {
+        let ptr = match self {
+            Literal::List(list) => Rc::as_ptr(list) as *const (),
+            Literal::Map(map) => Rc::as_ptr(map) as *const (),
+            // strings are quoted inside lists and maps, so that `["a, b"]` and `["a", "b"]` differ
+            Literal::Str(text) if !outer.is_empty() => return write!(f, "{:?}", text),
+            _ => return write!(f, "{}", self),
+        };
+        if outer.contains(&ptr) {
+            return f.write_str(match self {
+                Literal::List(_) => "[...]",
+                _ => "{...}",
+            });
+        }
+
+        outer.push(ptr);
+        match self {
+            Literal::List(list) => {
+                f.write_str("[")?;
+                for (i, element) in list.borrow().iter().enumerate() {
+                    if i > 0 {
+                        f.write_str(", ")?;
+                    }
+                    element.fmt_nested(f, outer)?;
+                }
+                f.write_str("]")?;
+            }
+            Literal::Map(map) => {
+                f.write_str("{")?;
+                for (i, (key, value)) in map.borrow().iter().enumerate() {
+                    if i > 0 {
+                        f.write_str(", ")?;
+                    }
+                    key.fmt_nested(f, outer)?;
+                    f.write_str(": ")?;
+                    value.fmt_nested(f, outer)?;
+                }
+                f.write_str("}")?;
+            }
+            _ => unreachable!("only lists and maps are nested"),
+        }
+        outer.pop();
+        Ok(())
+    }
+}
+
+impl From<BigInt> for Literal {
+    fn from(num: BigInt) -> Self {
+        match num.to_i64() {
+            Some(num) => Literal::Integer(num),
+            None => Literal::BigInt(num),
+        }
+    }
+}
+
+/// numbers compare by value, so `1 == 1.0`, and so do ranges. functions, natives, classes,
+/// instances, lists and maps compare by identity
+impl PartialEq for Literal {
+    fn eq(&self, other: &Literal) -> bool {
+        match (self, other) {
+            (Literal::Bool(left), Literal::Bool(right)) => left == right,
+            (Literal::Nil, Literal::Nil) => true,
+            (Literal::Number(left), Literal::Number(right)) => left == right,
+            (Literal::Integer(left), Literal::Integer(right)) => left == right,
+            (Literal::Integer(int), Literal::Number(float))
+            | (Literal::Number(float), Literal::Integer(int)) => {
+                // compare exactly rather than rounding the integer to the nearest float
+                float.fract() == 0.0
+                    && *float >= i64::MIN as f64
+                    && *float < i64::MAX as f64
+                    && *float as i64 == *int
+            }
+            (Literal::BigInt(left), Literal::BigInt(right)) => left == right,
+            (Literal::BigInt(big), Literal::Number(float))
+            | (Literal::Number(float), Literal::BigInt(big)) => {
+                BigInt::from_f64(*float).as_ref() == Some(big)
+            }
+            (Literal::Str(left), Literal::Str(right)) => left == right,
+            (Literal::Function(left), Literal::Function(right)) => left == right,
+            (Literal::Native(left), Literal::Native(right)) => left == right,
+            (Literal::Class(left), Literal::Class(right)) => left == right,
+            (Literal::Instance(left), Literal::Instance(right)) => Rc::ptr_eq(left, right),
+            (Literal::List(left), Literal::List(right)) => Rc::ptr_eq(left, right),
+            (Literal::Map(left), Literal::Map(right)) => Rc::ptr_eq(left, right),
+            (
+                Literal::Range {
+                    start,
+                    end,
+                    inclusive,
+                },
+                Literal::Range {
+                    start: other_start,
+                    end: other_end,
+                    inclusive: other_inclusive,
+                },
+            ) => start == other_start && end == other_end && inclusive == other_inclusive,
+            _ => false,
         }
     }
 }
 
 impl std::fmt::Display for Literal {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        f.write_str(match self {
-            Literal::Bool(true) => "true",
-            Literal::Bool(false) => "false",
-            Literal::Nil => "nil",
-            Literal::Number(_) => "<number>",
-            Literal::Str(text) => text,
-        })
+        match self {
+            Literal::Bool(true) => f.write_str("true"),
+            Literal::Bool(false) => f.write_str("false"),
+            Literal::Nil => f.write_str("nil"),
+            Literal::Number(num) => f.write_str(&format_number(*num)),
+            Literal::Integer(num) => write!(f, "{}", num),
+            Literal::BigInt(num) => write!(f, "{}", num),
+            Literal::Str(text) => f.write_str(text),
+            Literal::Function(function) => write!(f, "<fn {}>", function.name()),
+            Literal::Native(native) => write!(f, "<native fn {}>", native.name),
+            Literal::Class(class) => f.write_str(&class.name),
+            Literal::Instance(instance) => write!(f, "{} instance", instance.borrow().class.name),
+            Literal::List(_) | Literal::Map(_) => self.fmt_nested(f, &mut Vec::new()),
+            Literal::Range {
+                start,
+                end,
+                inclusive,
+            } => {
+                let operator = if *inclusive { "..=" } else { ".." };
+                write!(f, "{}{}{}", start, operator, end)
+            }
+        }
+    }
+}
+
+/// canonical text for a number, matching the reference implementation (jlox, which uses java's
+/// `Double.toString`): shortest round-trip digits, without a trailing `.0` on integral values.
+/// magnitudes from 10^7 up and below 10^-3 use exponent notation, e.g. `1.0E21` and `1.5E-7`.
+/// the non-finite values are `nan`, `inf` and `-inf`, where jlox would print `NaN` and
+/// `Infinity`
+pub fn format_number(num: f64) -> String {
+    if num.is_nan() {
+        return "nan".to_string();
+    }
+    let magnitude = num.abs();
+    if magnitude.is_finite() && magnitude != 0.0 && !(1e-3..1e7).contains(&magnitude) {
+        // rust's float formatting is already shortest round-trip, e.g. `1.5e-7`
+        let text = format!("{:e}", num);
+        let (mantissa, exponent) = text.split_once('e').expect("exponent notation has an 'e'");
+        let point = if mantissa.contains('.') { "" } else { ".0" };
+        format!("{}{}E{}", mantissa, point, exponent)
+    } else {
+        // prints infinities as `inf`, and integral values without a `.0`
+        num.to_string()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_format_number() {
+        let cases = [
+            (3.0, "3"),
+            (-0.0, "-0"),
+            (1.5, "1.5"),
+            (0.1 + 0.2, "0.30000000000000004"),
+            (1e21, "1.0E21"),
+            (-1e21, "-1.0E21"),
+            (1e-7, "1.0E-7"),
+            (1.5e300, "1.5E300"),
+            (12_345_678.0, "1.2345678E7"),
+            (9_999_999.0, "9999999"),
+            (1e7, "1.0E7"),
+            (0.001, "0.001"),
+            (0.000_25, "2.5E-4"),
+            (0.0, "0"),
+            (f64::NAN, "nan"),
+            (f64::INFINITY, "inf"),
+            (f64::NEG_INFINITY, "-inf"),
+        ];
+        for (num, expected) in cases {
+            assert_eq!(Literal::Number(num).to_string(), expected);
+        }
+    }
+
+    #[test]
+    fn test_mixed_equality() {
+        assert_eq!(Literal::Integer(1), Literal::Number(1.0));
+        assert_ne!(Literal::Integer(1), Literal::Number(1.5));
+        // 2^53 + 1 has no exact float representation
+        assert_ne!(
+            Literal::Integer(9_007_199_254_740_993),
+            Literal::Number(9_007_199_254_740_992.0)
+        );
+        assert_ne!(Literal::Integer(i64::MAX), Literal::Number(i64::MAX as f64));
+        assert_ne!(Literal::Integer(0), Literal::Number(f64::NAN));
+    }
+
+    #[test]
+    fn test_display_list() {
+        let inner = Literal::List(Rc::new(RefCell::new(vec![Literal::Str(
+            "a, b".to_string(),
+        )])));
+        let list = Rc::new(RefCell::new(vec![Literal::Integer(1), inner]));
+        list.borrow_mut().push(Literal::List(Rc::clone(&list)));
+        assert_eq!(Literal::List(list).to_string(), r#"[1, ["a, b"], [...]]"#);
     }
 }